@@ -61,11 +61,34 @@ export async function activate(context: ExtensionContext) {
             // Notify the server about file changes to '.clientrc files contained in the workspace
             fileEvents: workspace.createFileSystemWatcher("**/.clientrc")
         },
-        traceOutputChannel
+        traceOutputChannel,
+        initializationOptions: {
+            maxDiagnostics: workspace.getConfiguration("mythic").get("maxDiagnostics"),
+            pullDiagnostics: workspace.getConfiguration("mythic").get("pullDiagnostics"),
+            severityOverrides: workspace.getConfiguration("mythic").get("severityOverrides"),
+            logLevel: workspace.getConfiguration("mythic").get("logLevel"),
+            legacyDiagnosticCodes: workspace.getConfiguration("mythic").get("legacyDiagnosticCodes"),
+            maxPreloadedFiles: workspace.getConfiguration("mythic").get("maxPreloadedFiles"),
+            completion: {
+                triggerCharacters: workspace.getConfiguration("mythic").get("completion.triggerCharacters")
+            }
+        }
     };
 
     // Create the language client and start the client.
     client = new LanguageClient("mythic-language-server", "Mythic Language Server", serverOptions, clientOptions);
+    // The server sends plain JSON arguments, so convert them before handing them to VSCode's references view.
+    context.subscriptions.push(
+        commands.registerCommand("mythic.showReferences", (uri: string, position, locations: any[]) => {
+            const converter = client.protocol2CodeConverter;
+            return commands.executeCommand(
+                "editor.action.showReferences",
+                Uri.parse(uri),
+                converter.asPosition(position),
+                locations.map(location => converter.asLocation(location))
+            );
+        })
+    );
     // activateInlayHints(context);
     try {
         console.log("Starting client...");
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, TextEdit, WorkspaceEdit,
+};
+
+use crate::{
+    documents::DocumentInfo,
+    errors::error_registry::diagnostic_code,
+    mythic_parser::expressions::SkillLine,
+    utilities::positions_and_ranges::{CustomPosition, CustomRange},
+};
+
+/// Computes the code actions for a range of a document, e.g. the cursor or a selection.
+pub fn code_actions(doc: &DocumentInfo, range: &CustomRange) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    for (scalar_range, skill_line) in doc.skill_lines() {
+        wrap_inline_skills(doc, skill_line, &scalar_range, range, &mut actions);
+    }
+    remove_spaces_around_equals(doc, range, &mut actions);
+    actions
+}
+
+/// Offers to wrap inline skills that aren't in a list, e.g. `skill{s=damage{a=1}}`
+/// becomes `skill{s=[ - damage{a=1} ]}`. Skill lines nested in inline skills are included.
+fn wrap_inline_skills(
+    doc: &DocumentInfo,
+    skill_line: &SkillLine,
+    scalar_range: &CustomRange,
+    range: &CustomRange,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    for value in skill_line.bare_inline_skills() {
+        let value_range = match value.get_range() {
+            Some(value_range) => value_range.relative_to(&scalar_range.start),
+            None => continue,
+        };
+        if !overlaps(&value_range, range) {
+            continue;
+        }
+        let edit = TextEdit::new(
+            value_range.to_range(),
+            format!("[ - {} ]", value.to_source()),
+        );
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: String::from("Wrap in an inline skill list"),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(diagnostics_at(doc, &value_range, "bare_inline_skill_error")),
+            edit: Some(WorkspaceEdit::new(HashMap::from([(
+                doc.uri.clone(),
+                vec![edit],
+            )]))),
+            is_preferred: Some(true),
+            ..CodeAction::default()
+        }));
+    }
+    for inline_skill_line in skill_line.inline_skill_lines() {
+        wrap_inline_skills(doc, inline_skill_line, scalar_range, range, actions);
+    }
+}
+
+/// Offers to remove the spaces around an MLC's `=`, e.g. `amount = 5` becomes `amount=5`.
+/// The diagnostic's range spans from the end of the key to the start of the value.
+fn remove_spaces_around_equals(
+    doc: &DocumentInfo,
+    range: &CustomRange,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    for diagnostic in &doc.diagnostics {
+        if diagnostic_code(diagnostic) != Some("space_around_equals_error") {
+            continue;
+        }
+        let diagnostic_range = CustomRange::new(
+            CustomPosition::from_position(&diagnostic.range.start),
+            CustomPosition::from_position(&diagnostic.range.end),
+        );
+        if !overlaps(&diagnostic_range, range) {
+            continue;
+        }
+        let edit = TextEdit::new(diagnostic.range, String::from("="));
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: String::from("Remove the spaces around '='"),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(WorkspaceEdit::new(HashMap::from([(
+                doc.uri.clone(),
+                vec![edit],
+            )]))),
+            is_preferred: Some(true),
+            ..CodeAction::default()
+        }));
+    }
+}
+
+/// Whether two ranges share at least one position, including their ends.
+fn overlaps(a: &CustomRange, b: &CustomRange) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+/// Returns the document's diagnostics with the given code and range, which a quick fix resolves.
+fn diagnostics_at(doc: &DocumentInfo, range: &CustomRange, code: &str) -> Vec<Diagnostic> {
+    doc.diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic.range == range.to_range() && diagnostic_code(diagnostic) == Some(code)
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestServer;
+
+    /// Returns the text of every edit the code actions at a position would make.
+    fn new_texts(source: &str, position: CustomPosition) -> Vec<String> {
+        let server = TestServer::new();
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        code_actions(&doc, &CustomRange::new(position, position))
+            .into_iter()
+            .flat_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => action.edit.unwrap().changes.unwrap(),
+                CodeActionOrCommand::Command(_) => panic!("expected a code action"),
+            })
+            .flat_map(|(_, edits)| edits)
+            .map(|edit| edit.new_text)
+            .collect()
+    }
+
+    #[test]
+    fn wraps_bare_inline_skills_in_a_list() {
+        let source = "Mob:\n  Skills:\n  - skill{s=damage{a=1}} @self\n";
+        assert_eq!(
+            new_texts(source, CustomPosition::new(2, 15)),
+            vec![String::from("[ - damage{a=1} ]")]
+        );
+    }
+
+    #[test]
+    fn removes_the_spaces_around_equals() {
+        let source = "Mob:\n  Skills:\n  - damage{amount = 5} @self\n";
+        let server = TestServer::new();
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        let codes = doc
+            .diagnostics
+            .iter()
+            .filter_map(diagnostic_code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["space_around_equals_error"]);
+        assert_eq!(
+            new_texts(source, CustomPosition::new(2, 18)),
+            vec![String::from("=")]
+        );
+    }
+}
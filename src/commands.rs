@@ -0,0 +1,222 @@
+use serde_json::{json, Value};
+use tower_lsp::{
+    jsonrpc::{Error, Result},
+    lsp_types::Position,
+};
+
+use crate::{
+    formatting::format_document,
+    matching_bracket::matching_bracket,
+    mythic_parser::{
+        lexer::{dump_tokens, MythicScanner},
+        parse_skill_line,
+        validation::{validate, validate_targeter},
+    },
+    utilities::positions_and_ranges::CustomPosition,
+    Backend,
+};
+
+/// Returns the diagnostics of a document as JSON. Takes the document's URI as its only argument.
+pub const EXPORT_DIAGNOSTICS: &str = "mythic.exportDiagnostics";
+
+/// Returns the tokens of a skill line, one per line. Takes the skill line as its only argument.
+pub const DUMP_TOKENS: &str = "mythic.dumpTokens";
+
+/// Checks a skill line without needing an open document. Takes the skill line as its only
+/// argument. Returns its diagnostics like [`EXPORT_DIAGNOSTICS`], or an empty list if it's valid.
+pub const VALIDATE_SKILL: &str = "mythic.validateSkill";
+
+/// Formats the skill lines of every document the server knows of, see [`format_document`].
+/// Returns the edits by document URI, leaving out documents that are already formatted.
+pub const FORMAT_ALL: &str = "mythic.formatAll";
+
+/// Returns the position of the bracket matching the one at a position, e.g. to jump between them.
+/// Takes the document's URI and the position, see [`matching_bracket`].
+pub const MATCH_BRACKET: &str = "mythic.matchBracket";
+
+/// All commands supported by `workspace/executeCommand`.
+pub const COMMANDS: &[&str] = &[
+    EXPORT_DIAGNOSTICS,
+    DUMP_TOKENS,
+    VALIDATE_SKILL,
+    FORMAT_ALL,
+    MATCH_BRACKET,
+];
+
+/// Runs one of the [`COMMANDS`].
+pub fn execute(backend: &Backend, command: &str, arguments: &[Value]) -> Result<Option<Value>> {
+    match command {
+        EXPORT_DIAGNOSTICS => export_diagnostics(backend, arguments),
+        DUMP_TOKENS => dump_skill_line_tokens(arguments),
+        VALIDATE_SKILL => validate_skill(arguments),
+        FORMAT_ALL => format_all(backend),
+        MATCH_BRACKET => match_bracket(backend, arguments),
+        _ => Err(Error::invalid_params(format!(
+            "Unknown command: {}",
+            command
+        ))),
+    }
+}
+
+/// Gets the position argument at the given index, e.g. `{"line": 0, "character": 5}`.
+fn position_argument(arguments: &[Value], index: usize) -> Result<CustomPosition> {
+    arguments
+        .get(index)
+        .and_then(|argument| serde_json::from_value::<Position>(argument.clone()).ok())
+        .map(|position| CustomPosition::from_position(&position))
+        .ok_or_else(|| Error::invalid_params("Expected a position argument!"))
+}
+
+/// Gets the string argument at the given index.
+fn string_argument<'a>(arguments: &'a [Value], index: usize, name: &str) -> Result<&'a str> {
+    arguments
+        .get(index)
+        .and_then(|argument| argument.as_str())
+        .ok_or_else(|| Error::invalid_params(format!("Expected a {} argument!", name)))
+}
+
+fn export_diagnostics(backend: &Backend, arguments: &[Value]) -> Result<Option<Value>> {
+    let uri = string_argument(arguments, 0, "document URI")?;
+    let doc_info = match backend.document_map.get(uri) {
+        Some(doc_info) => doc_info,
+        None => return Ok(None),
+    };
+    let diagnostics = doc_info
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "range": diagnostic.range,
+                "severity": diagnostic.severity,
+                "code": diagnostic.code,
+                "message": diagnostic.message,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Some(Value::Array(diagnostics)))
+}
+
+fn format_all(backend: &Backend) -> Result<Option<Value>> {
+    let edits = backend
+        .document_map
+        .iter()
+        .filter_map(|doc_info| {
+            let edits = format_document(&doc_info);
+            if edits.is_empty() {
+                return None;
+            }
+            Some((doc_info.key().clone(), json!(edits)))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    Ok(Some(Value::Object(edits)))
+}
+
+fn match_bracket(backend: &Backend, arguments: &[Value]) -> Result<Option<Value>> {
+    let uri = string_argument(arguments, 0, "document URI")?;
+    let position = position_argument(arguments, 1)?;
+    let doc_info = match backend.document_map.get(uri) {
+        Some(doc_info) => doc_info,
+        None => return Ok(None),
+    };
+    Ok(matching_bracket(&doc_info, &position).map(|position| json!(position.to_position())))
+}
+
+fn dump_skill_line_tokens(arguments: &[Value]) -> Result<Option<Value>> {
+    let source = string_argument(arguments, 0, "skill line")?;
+    let tokens = MythicScanner::new(source.to_string()).scan_tokens();
+    Ok(Some(Value::String(dump_tokens(&tokens))))
+}
+
+fn validate_skill(arguments: &[Value]) -> Result<Option<Value>> {
+    let source = string_argument(arguments, 0, "skill line")?;
+    let errors = match parse_skill_line(source) {
+        Ok((skill_line, mut errors)) => {
+            errors.extend(validate(&skill_line));
+            errors.extend(validate_targeter(&skill_line));
+            errors
+        }
+        Err(error) => vec![error],
+    };
+    let diagnostics = errors
+        .iter()
+        .map(|error| {
+            json!({
+                "range": error.range.to_range(),
+                "severity": error.severity,
+                "code": (!error.code.is_empty()).then_some(error.code_number),
+                "message": error.message,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Some(Value::Array(diagnostics)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestServer;
+
+    #[test]
+    fn exports_every_diagnostic_of_a_document() {
+        let server = TestServer::new();
+        let uri = TestServer::uri("mobs.yml");
+        server.backend().update_document(
+            &uri,
+            String::from(
+                "Mob:\n  Skills:\n  - damage{amount=1;amount=2} @self\n  - damage{amount=1 @self\n",
+            ),
+        );
+        let exported = execute(server.backend(), EXPORT_DIAGNOSTICS, &[json!(uri)])
+            .unwrap()
+            .unwrap();
+        let diagnostics = exported.as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0]["code"], json!(7));
+        assert_eq!(diagnostics[1]["code"], json!(13));
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 2, "character": 20 })
+        );
+    }
+
+    #[test]
+    fn validates_skill_lines_without_a_document() {
+        let server = TestServer::new();
+        let validate = |source: &str| {
+            execute(server.backend(), VALIDATE_SKILL, &[json!(source)])
+                .unwrap()
+                .unwrap()
+        };
+        let diagnostics = validate("damage{amount=1 @self");
+        assert_eq!(
+            diagnostics.as_array().unwrap().len(),
+            1,
+            "{:?}",
+            diagnostics
+        );
+        assert_eq!(diagnostics[0]["code"], json!(13));
+        assert_eq!(validate("damage{amount=1} @self"), json!([]));
+    }
+
+    #[test]
+    fn formats_only_the_documents_that_need_it() {
+        let server = TestServer::new();
+        let formatted = TestServer::uri("formatted.yml");
+        let unformatted = TestServer::uri("unformatted.yml");
+        server.backend().update_document(
+            &formatted,
+            String::from("Mob:\n  Skills:\n  - damage{a=1} @self\n"),
+        );
+        server.backend().update_document(
+            &unformatted,
+            String::from("Mob:\n  Skills:\n  - damage{ a=1}  @self\n"),
+        );
+        let edits = execute(server.backend(), FORMAT_ALL, &[]).unwrap().unwrap();
+        let edits = edits.as_object().unwrap();
+        assert_eq!(edits.keys().collect::<Vec<_>>(), vec![unformatted.as_str()]);
+        assert_eq!(
+            edits[unformatted.as_str()][0]["newText"],
+            json!("damage{a=1} @self")
+        );
+    }
+}
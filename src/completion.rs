@@ -0,0 +1,286 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemTag, InsertTextFormat,
+};
+
+use crate::{
+    mythic_parser::{
+        catalog::catalog, placeholders::placeholder_children, triggers::TriggerArgument,
+    },
+    utilities::positions_and_ranges::CustomPosition,
+};
+
+/// The characters that make the client ask for completions while typing.
+pub const TRIGGER_CHARACTERS: &[&str] = &["~", "?", "!", "@", "."];
+
+/// A skill-line template offered at the start of a skill line.
+struct Snippet {
+    label: &'static str,
+    detail: &'static str,
+    body: &'static str,
+}
+
+const fn snippet(label: &'static str, detail: &'static str, body: &'static str) -> Snippet {
+    Snippet {
+        label,
+        detail,
+        body,
+    }
+}
+
+/// Templates for common skill lines, with tab stops for the parts that usually change.
+const SNIPPETS: &[Snippet] = &[
+    snippet(
+        "damage",
+        "Damage the target",
+        "damage{amount=$1} @${2:target}",
+    ),
+    snippet("heal", "Heal the caster", "heal{amount=$1} @${2:self}"),
+    snippet("message", "Send a message", "message{m=\"$1\"} @${2:self}"),
+    snippet("skill", "Run a metaskill", "skill{s=$1} @${2:self}"),
+    snippet(
+        "damage on timer",
+        "Damage nearby players periodically",
+        "damage{amount=$1} @PlayersInRadius{r=${2:10}} ~onTimer:${3:20}",
+    ),
+];
+
+/// Returns the text of a line before the given position, or `None` if the position is out of bounds.
+fn line_prefix(source: &Rope, position: &CustomPosition) -> Option<String> {
+    let line = source.get_line(position.line as usize)?;
+    Some(line.chars().take(position.character as usize).collect())
+}
+
+/// Computes the completions at a position in a document.
+/// The source text is used instead of the parsed skill lines, as the line being typed
+/// usually doesn't parse yet, e.g. `damage{a=1} ~`.
+pub fn completions(source: &Rope, position: &CustomPosition) -> Vec<CompletionItem> {
+    let prefix = match line_prefix(source, position) {
+        Some(prefix) => prefix,
+        None => return Vec::new(),
+    };
+    // skip the part of the word that has already been typed
+    let before_word = prefix.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+    if before_word.trim() == "-" {
+        let indent = before_word.len() - before_word.trim_start().len();
+        if in_skill_list(source, position.line as usize, indent) {
+            let mut items = snippet_completions();
+            items.extend(mechanic_completions());
+            return items;
+        }
+        return Vec::new();
+    }
+    // inline conditions can be negated with `!` and check the trigger with `~`, e.g. `?!~health`
+    if before_word.trim_end_matches(['!', '~']).ends_with('?') {
+        return condition_completions();
+    }
+    if before_word.ends_with('~') {
+        return trigger_completions();
+    }
+    if before_word.ends_with('@') {
+        return targeter_completions();
+    }
+    placeholder_completions(before_word)
+}
+
+/// Whether the list item on the given line belongs to a `Skills` list,
+/// i.e. the closest key above it that isn't indented further is `Skills:`.
+fn in_skill_list(source: &Rope, line: usize, indent: usize) -> bool {
+    for line in (0..line).rev() {
+        let text = match source.get_line(line) {
+            Some(text) => text.to_string(),
+            None => return false,
+        };
+        let trimmed = text.trim_start();
+        // blank lines, comments, and the other items of the list
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('-') {
+            continue;
+        }
+        let key_indent = text.len() - trimmed.len();
+        if key_indent > indent {
+            continue;
+        }
+        return trimmed.trim_end().eq_ignore_ascii_case("skills:");
+    }
+    false
+}
+
+/// Suggests the [`SNIPPETS`]. They use [`CompletionItemKind::SNIPPET`], so clients can
+/// filter them separately from the keyword completions.
+fn snippet_completions() -> Vec<CompletionItem> {
+    SNIPPETS
+        .iter()
+        .map(|snippet| CompletionItem {
+            label: snippet.label.to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(snippet.detail.to_string()),
+            insert_text: Some(snippet.body.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+/// Suggests every known mechanic by its canonical name. Aliases like `d` for `damage`
+/// are still accepted, but aren't listed to keep the suggestions short.
+/// Deprecated mechanics are tagged, so clients can strike them through.
+fn mechanic_completions() -> Vec<CompletionItem> {
+    catalog()
+        .mechanics
+        .iter()
+        .map(|mechanic| CompletionItem {
+            label: mechanic.names[0].clone(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: mechanic.description.clone(),
+            tags: mechanic
+                .deprecated
+                .as_ref()
+                .map(|_| vec![CompletionItemTag::DEPRECATED]),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+/// Suggests every known trigger. Triggers that require an argument insert a `:` and
+/// leave the cursor after it.
+fn trigger_completions() -> Vec<CompletionItem> {
+    catalog()
+        .triggers
+        .iter()
+        .map(|trigger| {
+            let mut item = CompletionItem {
+                label: trigger.name.clone(),
+                kind: Some(CompletionItemKind::EVENT),
+                detail: trigger.description.clone(),
+                ..CompletionItem::default()
+            };
+            if trigger.argument == TriggerArgument::Required {
+                item.insert_text = Some(format!("{}:$1", trigger.name));
+                item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            }
+            item
+        })
+        .collect()
+}
+
+/// Suggests every known targeter by its canonical name. Targeters that need options
+/// insert braces and leave the cursor inside them, while ones like `@Origin` are inserted as is.
+fn targeter_completions() -> Vec<CompletionItem> {
+    catalog()
+        .targeters
+        .iter()
+        .map(|targeter| {
+            let name = &targeter.names[0];
+            let mut item = CompletionItem {
+                label: name.clone(),
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: targeter.description.clone(),
+                ..CompletionItem::default()
+            };
+            if targeter.requires_mlc {
+                item.insert_text = Some(format!("{}{{$1}}", name));
+                item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            }
+            item
+        })
+        .collect()
+}
+
+/// Suggests the next segment of a placeholder being typed, e.g. `name` after `<caster.`.
+/// Nothing is suggested outside of placeholders or before the first `.`, as a lone `<`
+/// is usually a comparison, e.g. `<50%`.
+fn placeholder_completions(before_word: &str) -> Vec<CompletionItem> {
+    let path = match before_word.rsplit_once('<') {
+        Some((_, path)) if !path.contains('>') => path,
+        _ => return Vec::new(),
+    };
+    let path = match path.strip_suffix('.') {
+        Some(path) => path.split('.').collect::<Vec<_>>(),
+        None => return Vec::new(),
+    };
+    placeholder_children(&path)
+        .unwrap_or_default()
+        .iter()
+        .map(|placeholder| CompletionItem {
+            label: placeholder.name.clone(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: placeholder.description.clone(),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+/// Suggests every known condition. Only the name is inserted, so a typed `!` or `~` is kept.
+fn condition_completions() -> Vec<CompletionItem> {
+    catalog()
+        .conditions
+        .iter()
+        .map(|condition| CompletionItem {
+            label: condition.names[0].clone(),
+            kind: Some(CompletionItemKind::METHOD),
+            detail: condition.description.clone(),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Computes the completions at the end of a document.
+    fn complete(source: &str) -> Vec<CompletionItem> {
+        let source = Rope::from(source);
+        let line = source.len_lines() - 1;
+        let position = CustomPosition::new(line as u32, source.line(line).len_chars() as u32);
+        completions(&source, &position)
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn completes_triggers_after_a_tilde() {
+        let items = complete("Mob:\n  Skills:\n  - damage{a=1} ~");
+        assert!(labels(&items).contains(&"onDamaged"));
+        assert!(items
+            .iter()
+            .all(|item| item.kind == Some(CompletionItemKind::EVENT)));
+        let timer = items.iter().find(|item| item.label == "onTimer").unwrap();
+        assert_eq!(timer.insert_text.as_deref(), Some("onTimer:$1"));
+    }
+
+    #[test]
+    fn completes_conditions_after_a_question_mark() {
+        let items = complete("Mob:\n  Skills:\n  - damage{a=1} @self ?");
+        assert!(labels(&items).contains(&"health"));
+        // only the name is inserted, so the `!` isn't typed twice
+        let items = complete("Mob:\n  Skills:\n  - damage{a=1} @self ?!");
+        let health = items.iter().find(|item| item.label == "health").unwrap();
+        assert_eq!(health.kind, Some(CompletionItemKind::METHOD));
+        assert!(health.insert_text.is_none());
+    }
+
+    #[test]
+    fn completes_snippets_at_the_start_of_a_skill_line() {
+        let items = complete("Mob:\n  Skills:\n  - ");
+        assert!(items.iter().any(|item| {
+            item.kind == Some(CompletionItemKind::SNIPPET)
+                && item.insert_text_format == Some(InsertTextFormat::SNIPPET)
+                && item
+                    .insert_text
+                    .as_ref()
+                    .is_some_and(|text| text.contains("$1"))
+        }));
+        assert!(complete("Mob:\n  Drops:\n  - ").is_empty());
+    }
+
+    #[test]
+    fn completes_placeholder_paths() {
+        let items = complete("Mob:\n  Skills:\n  - message{m=<caster.");
+        assert!(labels(&items).contains(&"name"));
+        // unknown paths get nothing
+        assert!(complete("Mob:\n  Skills:\n  - message{m=<nothing.").is_empty());
+    }
+}
@@ -1,8 +1,18 @@
+use std::{collections::HashMap, sync::Arc};
+
 use marked_yaml::Node;
 use ropey::Rope;
-use tower_lsp::lsp_types::{Diagnostic, Hover, SemanticToken, SemanticTokenType};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, Hover, SemanticToken, SemanticTokenModifier, SemanticTokenType,
+    Url,
+};
 
-use crate::{utilities::positions_and_ranges::CustomRange, Backend};
+use crate::{
+    errors::error_registry::{diagnostic_code, use_legacy_code, Error},
+    mythic_parser::expressions::{ExprTrait, Placeholder, SkillLine},
+    settings::{SeverityOverride, OPT_IN_DIAGNOSTICS},
+    utilities::positions_and_ranges::{CustomPosition, CustomRange},
+};
 
 pub const LEGEND_TYPE: &[SemanticTokenType] = &[
     SemanticTokenType::NAMESPACE,
@@ -38,31 +48,588 @@ pub fn get_index_for_type(token_type: SemanticTokenType) -> u32 {
         .unwrap()
 }
 
-#[derive(Debug, Clone)]
+pub const LEGEND_MODIFIER: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DEPRECATED,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+    SemanticTokenModifier::MODIFICATION,
+];
+
+/// Encodes modifiers as a bitset, where each bit is a modifier's index in [`LEGEND_MODIFIER`].
+pub fn get_bitset_for_modifiers(modifiers: &[SemanticTokenModifier]) -> u32 {
+    modifiers
+        .iter()
+        .filter_map(|modifier| LEGEND_MODIFIER.iter().position(|x| x == modifier))
+        .fold(0, |bitset, index| bitset | 1 << index)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ImCompleteSemanticToken {
     pub start: usize,
     pub length: usize,
     pub token_type: u32,
+    /// The token's modifiers. See [`get_bitset_for_modifiers`].
+    pub modifiers: u32,
+}
+
+impl ImCompleteSemanticToken {
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
+/// Sorts tokens by their start and removes overlaps, which editors can't render.
+/// Where tokens overlap, the more specific (inner) one wins and the outer one is split around it,
+/// e.g. a YAML string token around the tokens of the skill line inside it.
+pub fn remove_overlapping_tokens(
+    tokens: &[ImCompleteSemanticToken],
+) -> Vec<ImCompleteSemanticToken> {
+    let mut sorted = tokens.to_vec();
+    // outer tokens come before the tokens they contain
+    sorted.sort_by(|a, b| a.start.cmp(&b.start).then(b.length.cmp(&a.length)));
+    let mut result = Vec::new();
+    // the tokens enclosing the current position, innermost last,
+    // each with the start of its part that hasn't been added yet
+    let mut open: Vec<(ImCompleteSemanticToken, usize)> = Vec::new();
+    let mut close = |token: &ImCompleteSemanticToken, from: usize, to: usize| {
+        if from < to {
+            result.push(ImCompleteSemanticToken {
+                start: from,
+                length: to - from,
+                token_type: token.token_type,
+                modifiers: token.modifiers,
+            });
+        }
+    };
+    for token in sorted.into_iter().filter(|token| token.length > 0) {
+        while let Some((outer, from)) = open.last() {
+            if outer.end() > token.start && outer.end() >= token.end() {
+                break;
+            }
+            // the outer token ends before this one, or only partially overlaps it
+            close(outer, *from, outer.end().min(token.start));
+            let end = outer.end();
+            open.pop();
+            if let Some((_, parent_from)) = open.last_mut() {
+                *parent_from = (*parent_from).max(end);
+            }
+        }
+        if let Some((outer, from)) = open.last_mut() {
+            close(outer, *from, token.start);
+            *from = token.end();
+        }
+        let start = token.start;
+        open.push((token, start));
+    }
+    while let Some((outer, from)) = open.pop() {
+        close(&outer, from, outer.end());
+        if let Some((_, parent_from)) = open.last_mut() {
+            *parent_from = (*parent_from).max(outer.end());
+        }
+    }
+    result.sort_by_key(|token| token.start);
+    result
+}
+
+/// A named metaskill definition or reference within a document.
+#[derive(Debug, Clone)]
+pub struct SkillSymbol {
+    pub name: String,
+    pub range: CustomRange,
+}
+
+impl SkillSymbol {
+    pub fn new(name: String, range: CustomRange) -> Self {
+        Self { name, range }
+    }
+}
+
+/// A metaskill defined at the top level of a document.
+#[derive(Debug, Clone)]
+pub struct SkillDefinition {
+    pub name: String,
+    /// The range of the metaskill's name.
+    pub range: CustomRange,
+    /// The range of the whole definition, up to the next top-level key.
+    pub body: CustomRange,
+}
+
+impl SkillDefinition {
+    pub fn new(name: String, range: CustomRange, body: CustomRange) -> Self {
+        Self { name, range, body }
+    }
+}
+
+/// The results of running the Mythic parser over a single skill line scalar.
+/// Everything is relative to the start of the scalar, so it can be reused after edits elsewhere.
+#[derive(Debug, Clone)]
+pub struct SkillScalar {
+    /// The byte offset of the scalar in the document.
+    pub offset: usize,
+    /// The scalar's value.
+    pub source: String,
+    /// The parsed skill line, shared between the versions of a document it's reused in.
+    pub skill_line: Arc<SkillLine>,
+    pub semantic_tokens: Vec<ImCompleteSemanticToken>,
+    pub errors: Vec<Error>,
+    pub skill_references: Vec<SkillSymbol>,
+}
+
+impl SkillScalar {
+    /// Adds this scalar's results to a document, given the scalar's start position in it.
+    pub fn apply(&self, doc: &mut DocumentInfo, start: &CustomPosition) {
+        doc.semantic_tokens
+            .extend(
+                self.semantic_tokens
+                    .iter()
+                    .map(|token| ImCompleteSemanticToken {
+                        start: token.start + self.offset,
+                        ..token.clone()
+                    }),
+            );
+        for error in &self.errors {
+            let diagnostic = error.relative_to(start).to_diagnostic(&doc.uri);
+            doc.push_diagnostic_dedup(diagnostic);
+        }
+        doc.skill_references
+            .extend(self.skill_references.iter().map(|reference| {
+                SkillSymbol::new(reference.name.clone(), reference.range.relative_to(start))
+            }));
+    }
 }
 
 /// Represents a cached document.
 #[derive(Debug, Clone)]
 pub struct DocumentInfo {
+    pub uri: Url,
     pub source: Rope,
     pub yaml: Option<Node>,
     pub hovers: Vec<Hover>,
     pub diagnostics: Vec<Diagnostic>,
     pub semantic_tokens: Vec<ImCompleteSemanticToken>,
+    /// [`Self::semantic_tokens`] encoded for the client, cached when the document is parsed.
+    /// See [`Self::encode_semantic_tokens`].
+    pub encoded_semantic_tokens: Vec<SemanticToken>,
+    /// Metaskills defined at the top level of this document.
+    pub skill_definitions: Vec<SkillDefinition>,
+    /// Metaskills called from this document, e.g. through `skill{s=Name}`.
+    pub skill_references: Vec<SkillSymbol>,
+    /// The skill line scalars parsed in this document.
+    pub skill_scalars: Vec<SkillScalar>,
+    /// Skill line scalars from the previous version of this document that weren't touched
+    /// by the latest edit, by their new offsets. These don't need to be parsed again.
+    /// Each is taken out when it's reused, and the rest are dropped once parsing is done.
+    pub reusable_skill_scalars: HashMap<usize, SkillScalar>,
 }
 
 impl DocumentInfo {
-    pub fn new(source: Rope, yaml: Option<Node>) -> Self {
+    pub fn new(uri: Url, source: Rope, yaml: Option<Node>) -> Self {
         Self {
+            uri,
             source,
             yaml,
             hovers: Vec::new(),
             diagnostics: Vec::new(),
             semantic_tokens: Vec::new(),
+            encoded_semantic_tokens: Vec::new(),
+            skill_definitions: Vec::new(),
+            skill_references: Vec::new(),
+            skill_scalars: Vec::new(),
+            reusable_skill_scalars: HashMap::new(),
+        }
+    }
+    /// Removes overlaps from the semantic tokens and delta-encodes them, as the client expects.
+    /// Tokens that lie outside the source are skipped.
+    pub fn encode_semantic_tokens(&self) -> Vec<SemanticToken> {
+        let rope = &self.source;
+        let mut pre_line = 0;
+        let mut pre_start = 0;
+        remove_overlapping_tokens(&self.semantic_tokens)
+            .iter()
+            .filter_map(|token| {
+                let line = rope.try_byte_to_line(token.start).ok()? as u32;
+                let first = rope.try_line_to_char(line as usize).ok()? as u32;
+                let start = rope.try_byte_to_char(token.start).ok()? as u32 - first;
+                let delta_line = line - pre_line;
+                let delta_start = if delta_line == 0 {
+                    start - pre_start
+                } else {
+                    start
+                };
+                let ret = Some(SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length: token.length as u32,
+                    token_type: token.token_type,
+                    token_modifiers_bitset: token.modifiers,
+                });
+                pre_line = line;
+                pre_start = start;
+                ret
+            })
+            .collect()
+    }
+    /// Adds a diagnostic, unless there already is one with the same range and code,
+    /// e.g. because two passes over the document found the same problem.
+    pub fn push_diagnostic_dedup(&mut self, diagnostic: Diagnostic) {
+        let duplicate = self
+            .diagnostics
+            .iter()
+            .any(|existing| existing.range == diagnostic.range && existing.code == diagnostic.code);
+        if !duplicate {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+    /// Changes the severity of every diagnostic whose code is in `overrides`,
+    /// removing the ones that are turned off.
+    /// The [`OPT_IN_DIAGNOSTICS`] are removed too, unless they're overridden.
+    pub fn apply_severity_overrides(&mut self, overrides: &HashMap<String, SeverityOverride>) {
+        self.diagnostics.retain_mut(|diagnostic| {
+            let code = match diagnostic_code(diagnostic) {
+                Some(code) => code,
+                None => return true,
+            };
+            match overrides.get(code) {
+                Some(severity_override) => match severity_override.severity() {
+                    Some(severity) => {
+                        diagnostic.severity = Some(severity);
+                        true
+                    }
+                    None => false,
+                },
+                None => !OPT_IN_DIAGNOSTICS.contains(&code),
+            }
+        });
+    }
+    /// Converts every diagnostic to the legacy `"<number>: <code>"` codes, see [`use_legacy_code`].
+    pub fn use_legacy_diagnostic_codes(&mut self) {
+        self.diagnostics.iter_mut().for_each(use_legacy_code);
+    }
+    /// Keeps only the first `max` diagnostics, replacing the rest with a single one saying how many were left out.
+    /// That summary isn't counted towards `max`, so e.g. a `max` of 0 still leaves the summary.
+    pub fn limit_diagnostics(&mut self, max: usize) {
+        if self.diagnostics.len() <= max {
+            return;
         }
+        let suppressed = self.diagnostics.split_off(max);
+        let range = suppressed[0].range;
+        let range = CustomRange::new(
+            CustomPosition::from_position(&range.start),
+            CustomPosition::from_position(&range.end),
+        );
+        let message = match suppressed.len() {
+            1 => String::from("1 more diagnostic suppressed."),
+            count => format!("{} more diagnostics suppressed.", count),
+        };
+        self.diagnostics.push(
+            Error::builder(range, message)
+                .severity(DiagnosticSeverity::INFORMATION)
+                .build()
+                .to_diagnostic(&self.uri),
+        );
+    }
+    /// Returns every skill line in this document along with the range of its scalar.
+    /// These are parsed once per version of the document, when the document is parsed.
+    pub fn skill_lines(&self) -> Vec<(CustomRange, &SkillLine)> {
+        let source = self.source.to_string();
+        self.skill_scalars
+            .iter()
+            .map(|scalar| {
+                let end = scalar.offset + scalar.source.len();
+                let range = CustomRange::new(
+                    CustomPosition::from_offset(scalar.offset as u32, &source),
+                    CustomPosition::from_offset(end as u32, &source),
+                );
+                (range, scalar.skill_line.as_ref())
+            })
+            .collect()
+    }
+    /// Finds the skill line whose scalar contains a position, along with the scalar's range.
+    /// Positions inside the skill line are relative to the range's start, see
+    /// [`CustomPosition::relative_from`].
+    pub fn skill_at(&self, position: &CustomPosition) -> Option<(CustomRange, &SkillLine)> {
+        self.skill_lines()
+            .into_iter()
+            .find(|(range, _)| range.start <= *position && *position <= range.end)
+    }
+    /// Returns every placeholder used in this document's skill lines, with its range in the document.
+    pub fn placeholders(&self) -> Vec<(CustomRange, &Placeholder)> {
+        self.skill_lines()
+            .into_iter()
+            .flat_map(|(range, skill_line)| {
+                skill_line
+                    .placeholders()
+                    .into_iter()
+                    .map(move |placeholder| {
+                        (
+                            placeholder.get_range().relative_to(&range.start),
+                            placeholder,
+                        )
+                    })
+            })
+            .collect()
+    }
+    /// Finds the skill line scalars of a previous version of this document
+    /// that lie outside the edited region, so they can be reused when parsing.
+    pub fn reuse_skill_scalars(&mut self, previous: &DocumentInfo) {
+        let old = previous.source.to_string();
+        let new = self.source.to_string();
+        // the edited region is whatever is between the common prefix and the common suffix
+        let prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.reusable_skill_scalars = previous
+            .skill_scalars
+            .iter()
+            .filter_map(|scalar| {
+                let offset = if scalar.offset + scalar.source.len() <= prefix {
+                    scalar.offset
+                } else if scalar.offset >= old.len() - suffix {
+                    scalar.offset + new.len() - old.len()
+                } else {
+                    return None;
+                };
+                Some((
+                    offset,
+                    SkillScalar {
+                        offset,
+                        ..scalar.clone()
+                    },
+                ))
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::error_registry::DuplicateMlcKeyError, testing::TestServer};
+
+    #[test]
+    fn reparses_only_the_edited_skill_line() {
+        let server = TestServer::new();
+        let uri = TestServer::uri("mobs.yml");
+        let source = "A:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=1} @target\n\
+                      B:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=2} @target\n\
+                      C:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=3} @target\n";
+        let before = server.backend().update_document(&uri, source.to_string());
+        let edited = source.replace("amount=2} @target", "amount=20} @trigger");
+        let after = server.backend().update_document(&uri, edited);
+
+        assert_eq!(after.skill_scalars.len(), 3);
+        let reused = |index: usize| {
+            Arc::ptr_eq(
+                &before.skill_scalars[index].skill_line,
+                &after.skill_scalars[index].skill_line,
+            )
+        };
+        assert!(reused(0));
+        assert!(!reused(1));
+        assert!(reused(2));
+        // the last scalar moved by the length of the edit, but its tokens didn't change
+        assert_eq!(
+            before.skill_scalars[2].semantic_tokens,
+            after.skill_scalars[2].semantic_tokens
+        );
+        assert_eq!(
+            after.skill_scalars[2].offset,
+            before.skill_scalars[2].offset + 2
+        );
+        assert_ne!(
+            before.skill_scalars[1].semantic_tokens,
+            after.skill_scalars[1].semantic_tokens
+        );
+    }
+
+    #[test]
+    fn lists_every_skill_line_with_its_range() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from(
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=1} @target\n  \
+                 - heal{amount=2} @self\n  - message{m=hi} @self\n",
+            ),
+        );
+        let skill_lines = doc
+            .skill_lines()
+            .into_iter()
+            .map(|(range, skill_line)| {
+                let range = (range.start.line, range.start.character, range.end.character);
+                (range, skill_line.mechanic().name().value())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            skill_lines,
+            vec![
+                ((3, 4, 28), String::from("damage")),
+                ((4, 4, 24), String::from("heal")),
+                ((5, 4, 23), String::from("message")),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_outer_tokens_around_inner_ones() {
+        let token = |start, length, token_type| ImCompleteSemanticToken {
+            start,
+            length,
+            token_type,
+            modifiers: 0,
+        };
+        let tokens = remove_overlapping_tokens(&[token(5, 3, 1), token(0, 20, 0), token(6, 1, 2)]);
+        assert_eq!(
+            tokens,
+            vec![
+                token(0, 5, 0),
+                token(5, 1, 1),
+                token(6, 1, 2),
+                token(7, 1, 1),
+                token(8, 12, 0),
+            ]
+        );
+        assert!(tokens.windows(2).all(|pair| pair[0].end() <= pair[1].start));
+    }
+
+    #[test]
+    fn lists_every_placeholder_with_its_range() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from(
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - message{m=<caster.name>} @self\n  \
+                 - message{m=<target.hp>} @self\n",
+            ),
+        );
+        let placeholders = doc
+            .placeholders()
+            .into_iter()
+            .map(|(range, placeholder)| {
+                let range = (range.start.line, range.start.character, range.end.character);
+                (range, placeholder.to_source())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            placeholders,
+            vec![
+                ((3, 14, 27), String::from("<caster.name>")),
+                ((4, 14, 25), String::from("<target.hp>")),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_skill_line_at_a_position() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from(
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=1} @target\n  \
+                 - heal{amount=2} @self\n",
+            ),
+        );
+        let (range, skill_line) = doc.skill_at(&CustomPosition::new(4, 12)).unwrap();
+        assert_eq!(skill_line.mechanic().name().value(), "heal");
+        assert_eq!((range.start.line, range.start.character), (4, 4));
+        assert!(doc.skill_at(&CustomPosition::new(2, 4)).is_none());
+    }
+
+    #[test]
+    fn caches_the_encoded_semantic_tokens() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from(
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=1} @target\n\
+                 Other:\n  Type: SKELETON\n  Skills:\n  - message{m=<caster.name>} @self\n",
+            ),
+        );
+        assert!(!doc.encoded_semantic_tokens.is_empty());
+        assert_eq!(doc.encoded_semantic_tokens, doc.encode_semantic_tokens());
+    }
+
+    #[test]
+    fn skips_duplicate_diagnostics() {
+        let uri = TestServer::uri("mobs.yml");
+        let mut doc = DocumentInfo::new(uri.clone(), Rope::from("a: b\n"), None);
+        let range = CustomRange::new(CustomPosition::new(0, 0), CustomPosition::new(0, 1));
+        let diagnostic = DuplicateMlcKeyError::new(range, String::from("a"))
+            .to_error()
+            .to_diagnostic(&uri);
+        doc.push_diagnostic_dedup(diagnostic.clone());
+        doc.push_diagnostic_dedup(diagnostic);
+        assert_eq!(doc.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn summarizes_every_diagnostic_over_the_limit() {
+        let uri = TestServer::uri("mobs.yml");
+        let mut doc = DocumentInfo::new(uri.clone(), Rope::from("a: b\nc: d\n"), None);
+        for line in 0..2 {
+            let range =
+                CustomRange::new(CustomPosition::new(line, 0), CustomPosition::new(line, 1));
+            doc.diagnostics.push(
+                DuplicateMlcKeyError::new(range, String::from("a"))
+                    .to_error()
+                    .to_diagnostic(&uri),
+            );
+        }
+        // the summary isn't counted towards the limit
+        doc.limit_diagnostics(0);
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_eq!(doc.diagnostics[0].message, "2 more diagnostics suppressed.");
+        assert_eq!(doc.diagnostics[0].range.start.line, 0);
+    }
+
+    #[test]
+    fn drops_the_skill_scalars_that_werent_reused() {
+        let server = TestServer::new();
+        let uri = TestServer::uri("mobs.yml");
+        let source = "A:\n  Skills:\n  - damage{amount=1} @self\n  - damage{amount=2} @self\n";
+        let before = server.backend().update_document(&uri, source.to_string());
+        // the second skill line is removed, so its scalar has nowhere to be reused
+        let after = server.backend().update_document(
+            &uri,
+            String::from("A:\n  Skills:\n  - damage{amount=1} @self\n"),
+        );
+        assert_eq!(after.skill_scalars.len(), 1);
+        assert!(Arc::ptr_eq(
+            &before.skill_scalars[0].skill_line,
+            &after.skill_scalars[0].skill_line
+        ));
+        assert!(after.reusable_skill_scalars.is_empty());
+    }
+
+    #[test]
+    fn hides_opt_in_diagnostics_unless_overridden() {
+        let server = TestServer::new();
+        let source = "Mob:\n  Skills:\n  - skill{s=[ - damage{a=1} ~onAttack ]} @self\n";
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        assert!(doc.diagnostics.is_empty());
+
+        let mut doc = DocumentInfo::new(TestServer::uri("mobs.yml"), Rope::from(source), None);
+        crate::yaml::parser::parse(server.backend(), &mut doc);
+        let overrides = HashMap::from([(
+            String::from("inline_skill_trigger_error"),
+            SeverityOverride::Warning,
+        )]);
+        doc.apply_severity_overrides(&overrides);
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_eq!(
+            doc.diagnostics[0].severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
     }
 }
@@ -1,14 +1,118 @@
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag,
+    Location, NumberOrString, Url,
+};
 
-use crate::utilities::positions_and_ranges::CustomRange;
+use crate::utilities::positions_and_ranges::{CustomPosition, CustomRange};
+
+/// The source of every diagnostic. Diagnostics of errors with a code append it, see [`Error::to_diagnostic`].
+pub const DIAGNOSTIC_SOURCE: &str = "Mythic Language Server";
+
+/// Returns the code of a diagnostic created by [`Error::to_diagnostic`], e.g. `syntax_error`,
+/// including diagnostics converted by [`use_legacy_code`].
+pub fn diagnostic_code(diagnostic: &Diagnostic) -> Option<&str> {
+    match (&diagnostic.code, &diagnostic.source) {
+        (Some(NumberOrString::String(code)), _) => Some(
+            code.split_once(": ")
+                .map_or(code.as_str(), |(_, code)| code),
+        ),
+        (Some(NumberOrString::Number(_)), Some(source)) => source
+            .strip_prefix(DIAGNOSTIC_SOURCE)?
+            .strip_prefix(" (")?
+            .strip_suffix(')'),
+        _ => None,
+    }
+}
+
+/// Converts a diagnostic to the legacy format, where its code is a single string
+/// like `"0: syntax_error"` and the source is just [`DIAGNOSTIC_SOURCE`].
+pub fn use_legacy_code(diagnostic: &mut Diagnostic) {
+    let number = match diagnostic.code {
+        Some(NumberOrString::Number(number)) => number,
+        _ => return,
+    };
+    let code = match diagnostic_code(diagnostic) {
+        Some(code) => format!("{}: {}", number, code),
+        None => return,
+    };
+    diagnostic.code = Some(NumberOrString::String(code));
+    diagnostic.source = Some(String::from(DIAGNOSTIC_SOURCE));
+}
 
 /// Should not be directly used.
+#[derive(Clone, Debug)]
 pub struct Error {
     pub message: String,
     pub range: CustomRange,
     pub severity: DiagnosticSeverity,
-    pub code: String,
+    pub code: &'static str,
     pub code_number: i32,
+    pub tags: Vec<DiagnosticTag>,
+    /// Boxed, as this is rarely set and errors are returned by value throughout the parser.
+    pub code_description: Option<Box<CodeDescription>>,
+    pub related_information: Vec<RelatedInformation>,
+}
+
+/// A location related to an error, e.g. where a duplicated value was first defined.
+#[derive(Clone, Debug)]
+pub struct RelatedInformation {
+    /// The document of the location, or `None` if it's in the same document as the error.
+    pub uri: Option<Url>,
+    pub range: CustomRange,
+    pub message: String,
+}
+
+impl RelatedInformation {
+    /// Creates related information in the same document as the error.
+    pub fn new(range: CustomRange, message: String) -> Self {
+        Self {
+            uri: None,
+            range,
+            message,
+        }
+    }
+    pub fn to_diagnostic_related_information(&self, uri: &Url) -> DiagnosticRelatedInformation {
+        DiagnosticRelatedInformation {
+            location: Location::new(
+                self.uri.clone().unwrap_or_else(|| uri.clone()),
+                self.range.to_range(),
+            ),
+            message: self.message.clone(),
+        }
+    }
+}
+
+/// Builds an [`Error`] that doesn't need its own type, e.g. for lint-style checks.
+/// See [`Error::builder`].
+pub struct ErrorBuilder {
+    error: Error,
+}
+
+impl ErrorBuilder {
+    pub fn code(mut self, code: &'static str, code_number: i32) -> Self {
+        self.error.code = code;
+        self.error.code_number = code_number;
+        self
+    }
+    pub fn severity(mut self, severity: DiagnosticSeverity) -> Self {
+        self.error.severity = severity;
+        self
+    }
+    pub fn tags(mut self, tags: Vec<DiagnosticTag>) -> Self {
+        self.error.tags = tags;
+        self
+    }
+    pub fn code_description(mut self, code_description: CodeDescription) -> Self {
+        self.error.code_description = Some(Box::new(code_description));
+        self
+    }
+    pub fn related_information(mut self, related_information: Vec<RelatedInformation>) -> Self {
+        self.error.related_information = related_information;
+        self
+    }
+    pub fn build(self) -> Error {
+        self.error
+    }
 }
 
 impl Error {
@@ -16,7 +120,7 @@ impl Error {
         message: String,
         range: CustomRange,
         severity: DiagnosticSeverity,
-        code: String,
+        code: &'static str,
         code_number: i32,
     ) -> Self {
         Self {
@@ -25,22 +129,88 @@ impl Error {
             severity,
             code,
             code_number,
+            tags: Vec::new(),
+            code_description: None,
+            related_information: Vec::new(),
+        }
+    }
+    /// Starts building an error with the given range and message.
+    /// Unless overridden, it is an error without a code.
+    pub fn builder(range: CustomRange, message: String) -> ErrorBuilder {
+        ErrorBuilder {
+            error: Self::new(message, range, DiagnosticSeverity::ERROR, "", 0),
         }
     }
-    pub fn to_diagnostic(&self) -> Diagnostic {
+    /// Overrides the severity, e.g. for errors that should only be warnings.
+    pub fn with_severity(mut self, severity: DiagnosticSeverity) -> Self {
+        self.severity = severity;
+        self
+    }
+    /// Adds tags that change how the editor renders the diagnostic, e.g. striking through deprecated code.
+    pub fn with_tags(mut self, tags: Vec<DiagnosticTag>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+    /// Adds a related location, e.g. where a duplicated value was first defined.
+    pub fn with_related_information(mut self, information: RelatedInformation) -> Self {
+        self.related_information.push(information);
+        self
+    }
+    /// Moves an error found in an embedded source (e.g. a skill line in a YAML scalar)
+    /// into the enclosing document. See [`CustomRange::relative_to`].
+    /// Related information in the same document is moved along with it.
+    pub fn relative_to(&self, origin: &CustomPosition) -> Self {
+        Self {
+            range: self.range.relative_to(origin),
+            related_information: self
+                .related_information
+                .iter()
+                .map(|information| match information.uri {
+                    Some(_) => information.clone(),
+                    None => RelatedInformation {
+                        range: information.range.relative_to(origin),
+                        ..information.clone()
+                    },
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+    /// Converts this error to a diagnostic in the document with the given URI.
+    /// The diagnostic's code is the error's code number, and its source names the code,
+    /// e.g. `Mythic Language Server (syntax_error)`.
+    pub fn to_diagnostic(&self, uri: &Url) -> Diagnostic {
+        let (code, source) = if self.code.is_empty() {
+            (None, String::from(DIAGNOSTIC_SOURCE))
+        } else {
+            (
+                Some(NumberOrString::Number(self.code_number)),
+                format!("{} ({})", DIAGNOSTIC_SOURCE, self.code),
+            )
+        };
         Diagnostic {
             range: self.range.to_range(),
             severity: Some(self.severity),
-            code: Some(NumberOrString::String(format!(
-                "{}: {}",
-                self.code_number, self.code
-            ))),
-            source: Some(String::from("Mythic Language Server")),
+            code,
+            source: Some(source),
             message: self.message.clone(),
-            related_information: None,
-            tags: None,
+            related_information: if self.related_information.is_empty() {
+                None
+            } else {
+                Some(
+                    self.related_information
+                        .iter()
+                        .map(|information| information.to_diagnostic_related_information(uri))
+                        .collect(),
+                )
+            },
+            tags: if self.tags.is_empty() {
+                None
+            } else {
+                Some(self.tags.clone())
+            },
             data: None,
-            code_description: None,
+            code_description: self.code_description.as_deref().cloned(),
         }
     }
 }
@@ -61,13 +231,9 @@ macro_rules! error_struct {
                 }
             }
             pub fn to_error(&self) -> Error {
-                Error::new(
-                    self.message.clone(),
-                    self.range,
-                    DiagnosticSeverity::ERROR,
-                    String::from($code),
-                    $code_number,
-                )
+                Error::builder(self.range, self.message.clone())
+                    .code($code, $code_number)
+                    .build()
             }
         }
     };
@@ -94,13 +260,9 @@ macro_rules! error_struct {
                 }
             }
             pub fn to_error(&self) -> Error {
-                Error::new(
-                    self.message.clone(),
-                    self.range,
-                    DiagnosticSeverity::ERROR,
-                    String::from($code),
-                    $code_number,
-                )
+                Error::builder(self.range, self.message.clone())
+                    .code($code, $code_number)
+                    .build()
             }
         }
     };
@@ -119,13 +281,9 @@ macro_rules! error_struct {
                 }
             }
             pub fn to_error(&self) -> Error {
-                Error::new(
-                    self.message.clone(),
-                    self.range,
-                    DiagnosticSeverity::ERROR,
-                    String::from($code),
-                    $code_number,
-                )
+                Error::builder(self.range, self.message.clone())
+                    .code($code, $code_number)
+                    .build()
             }
         }
     };
@@ -155,4 +313,240 @@ error_struct!(
     got,
     expected
 );
+error_struct!(
+    NestingTooDeepError,
+    4,
+    "nesting_too_deep_error",
+    |max_depth| format!(
+        "This skill line is nested too deeply! The maximum depth is {}.",
+        max_depth
+    ),
+    max_depth
+);
+error_struct!(
+    TriggerArgumentRequiredError,
+    5,
+    "trigger_argument_required_error",
+    |trigger| format!(
+        "The trigger {} requires an argument, e.g. ~{}:<argument>!",
+        trigger, trigger
+    ),
+    trigger
+);
+error_struct!(
+    TriggerArgumentForbiddenError,
+    6,
+    "trigger_argument_forbidden_error",
+    |trigger| format!("The trigger {} does not take an argument!", trigger),
+    trigger
+);
+error_struct!(
+    DuplicateMlcKeyError,
+    7,
+    "duplicate_mlc_key_error",
+    |key| format!(
+        "The key {} is already defined in this MLC! Only the last value will be used.",
+        key
+    ),
+    key
+);
+error_struct!(
+    DeprecatedMechanicError,
+    8,
+    "deprecated_mechanic_error",
+    |mechanic, replacement| format!(
+        "The mechanic {} is deprecated! Use {} instead.",
+        mechanic, replacement
+    ),
+    mechanic,
+    replacement
+);
+error_struct!(
+    UnnecessaryChanceError,
+    9,
+    "unnecessary_chance_error",
+    "This chance always succeeds, so it can be removed."
+);
+error_struct!(
+    InvalidAttributeTypeError,
+    10,
+    "invalid_attribute_type_error",
+    |attribute, expected| format!(
+        "Invalid value for the attribute {}! Expected {}.",
+        attribute, expected
+    ),
+    attribute,
+    expected
+);
+error_struct!(
+    UnterminatedPlaceholderError,
+    11,
+    "unterminated_placeholder_error",
+    "Unterminated placeholder! Expected a '>' to close it."
+);
+error_struct!(
+    DuplicateConditionMarkerError,
+    12,
+    "duplicate_condition_marker_error",
+    |marker| format!(
+        "'{}' can only be used once per inline condition! Use ?, ?!, ?~, ?!~, or ?~!.",
+        marker
+    ),
+    marker
+);
+error_struct!(
+    UnexpectedEofError,
+    13,
+    "unexpected_eof_error",
+    |expected| format!("Unexpected end of the skill line! Expected {}.", expected),
+    expected
+);
+error_struct!(
+    MissingTargeterError,
+    14,
+    "missing_targeter_error",
+    |mechanic| format!("The mechanic {} needs a targeter, e.g. @target!", mechanic),
+    mechanic
+);
+error_struct!(
+    AttributeOutOfRangeError,
+    15,
+    "attribute_out_of_range_error",
+    |attribute, expected| format!("The attribute {} must be {}!", attribute, expected),
+    attribute,
+    expected
+);
+error_struct!(
+    UnresolvedSkillReferenceError,
+    16,
+    "unresolved_skill_reference_error",
+    |skill| format!(
+        "The metaskill {} isn't defined in any file of the workspace!",
+        skill
+    ),
+    skill
+);
+error_struct!(
+    ImpossibleChanceError,
+    17,
+    "impossible_chance_error",
+    |chance| format!(
+        "The chance {} is impossible! Use a value from 0 to 1, or from 0% to 100%.",
+        chance
+    ),
+    chance
+);
+error_struct!(
+    BareInlineSkillError,
+    18,
+    "bare_inline_skill_error",
+    |skill| {
+        format!(
+        "Inline skills must be in a list, e.g. [ - {} ]! Otherwise this is read as a metaskill name.",
+        skill
+    )
+    },
+    skill
+);
+error_struct!(
+    SpaceAroundEqualsError,
+    19,
+    "space_around_equals_error",
+    "Spaces aren't allowed around '=' in an MLC!"
+);
+error_struct!(
+    MissingTargeterMlcError,
+    20,
+    "missing_targeter_mlc_error",
+    |targeter| format!(
+        "The targeter @{} needs options, e.g. @{}{{...}}!",
+        targeter, targeter
+    ),
+    targeter
+);
+error_struct!(
+    DuplicateKeyError,
+    21,
+    "duplicate_key_error",
+    |key| format!(
+        "The key {} is already defined in this mapping! Only the last value will be used.",
+        key
+    ),
+    key
+);
+error_struct!(
+    UnterminatedInlineSkillError,
+    22,
+    "unterminated_inline_skill_error",
+    "Unterminated inline skill! Expected a ']' to close it."
+);
+error_struct!(
+    InlineSkillTriggerError,
+    23,
+    "inline_skill_trigger_error",
+    "Triggers are ignored in inline skills! They run whenever the skill line around them does."
+);
+error_struct!(
+    InvalidTriggerArgumentError,
+    24,
+    "invalid_trigger_argument_error",
+    |trigger, expected| format!(
+        "Invalid argument for the trigger {}! Expected {}.",
+        trigger, expected
+    ),
+    trigger,
+    expected
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TestServer;
 
+    use super::*;
+
+    #[test]
+    fn builds_errors_with_a_severity_and_related_information() {
+        let range =
+            |line| CustomRange::new(CustomPosition::new(line, 0), CustomPosition::new(line, 4));
+        let uri = TestServer::uri("mobs.yml");
+        let diagnostic = Error::builder(range(1), String::from("Something's off."))
+            .severity(DiagnosticSeverity::WARNING)
+            .related_information(vec![RelatedInformation::new(
+                range(0),
+                String::from("Because of this."),
+            )])
+            .build()
+            .to_diagnostic(&uri);
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostic.related_information,
+            Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri, range(0).to_range()),
+                message: String::from("Because of this."),
+            }])
+        );
+    }
+
+    #[test]
+    fn sends_the_code_as_a_number() {
+        let range = CustomRange::new(CustomPosition::new(0, 0), CustomPosition::new(0, 1));
+        let error = DuplicateMlcKeyError::new(range, String::from("amount")).to_error();
+        let mut diagnostic = error.to_diagnostic(&TestServer::uri("mobs.yml"));
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::Number(error.code_number))
+        );
+        assert_eq!(
+            diagnostic_code(&diagnostic),
+            Some("duplicate_mlc_key_error")
+        );
+
+        use_legacy_code(&mut diagnostic);
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String(String::from(
+                "7: duplicate_mlc_key_error"
+            )))
+        );
+    }
+}
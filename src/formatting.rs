@@ -0,0 +1,47 @@
+use tower_lsp::lsp_types::TextEdit;
+
+use crate::{
+    documents::DocumentInfo,
+    mythic_parser::parse_skill_line,
+    utilities::positions_and_ranges::{CustomPosition, CustomRange},
+};
+
+/// Computes the edits that rewrite a document's skill lines in their canonical form,
+/// e.g. `damage{ a=1}  @self` becomes `damage{a=1} @self`. See [`SkillLine::to_source`].
+/// Skill lines with errors are left alone, as are quoted ones with escapes, whose value
+/// isn't what's written in the document.
+///
+/// [`SkillLine::to_source`]: crate::mythic_parser::expressions::SkillLine::to_source
+pub fn format_document(doc: &DocumentInfo) -> Vec<TextEdit> {
+    let source = doc.source.to_string();
+    let mut edits = Vec::new();
+    for scalar in &doc.skill_scalars {
+        if !scalar.errors.is_empty() {
+            continue;
+        }
+        let end = scalar.offset + scalar.source.len();
+        if source.get(scalar.offset..end) != Some(scalar.source.as_str()) {
+            continue;
+        }
+        let formatted = scalar.skill_line.to_source();
+        if formatted == scalar.source || !is_canonical(&formatted) {
+            continue;
+        }
+        let start = CustomPosition::from_offset(scalar.offset as u32, &source);
+        let end = CustomPosition::from_offset(end as u32, &source);
+        edits.push(TextEdit::new(
+            CustomRange::new(start, end).to_range(),
+            formatted,
+        ));
+    }
+    edits
+}
+
+/// Whether a formatted skill line parses without errors and stays the same when formatted again,
+/// so formatting never breaks a skill line that was fine before.
+fn is_canonical(formatted: &str) -> bool {
+    match parse_skill_line(formatted) {
+        Ok((skill_line, errors)) => errors.is_empty() && skill_line.to_source() == formatted,
+        Err(_) => false,
+    }
+}
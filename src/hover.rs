@@ -0,0 +1,310 @@
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind};
+
+use crate::{
+    documents::DocumentInfo,
+    mythic_parser::{
+        expressions::{
+            ExprTrait, HealthModifier, HealthModifierValue, HealthModifierValueOrRange, NodeRef,
+            SkillLine, TargeterName,
+        },
+        lexer::TokenType,
+        mechanics::{mechanic, Attribute, Mechanic},
+        targeters::{targeter, TargeterInfo},
+    },
+    utilities::positions_and_ranges::{CustomPosition, CustomRange},
+    yaml::parser::node_path,
+};
+
+/// Computes the hover at a position in a document, or `None` if there's nothing to describe.
+/// Anything without a richer hover shows where it is in the YAML instead.
+pub fn hover(doc: &DocumentInfo, position: &CustomPosition) -> Option<Hover> {
+    skill_line_hover(doc, position).or_else(|| yaml_path_hover(doc, position))
+}
+
+/// Describes the part of a skill line at a position, e.g. its mechanic or targeter.
+fn skill_line_hover(doc: &DocumentInfo, position: &CustomPosition) -> Option<Hover> {
+    let (range, skill_line) = doc.skill_at(position)?;
+    let relative_position = position.relative_from(&range.start);
+    let (contents, hover_range) = mechanic_hover(skill_line, &relative_position)
+        .or_else(|| targeter_hover(skill_line, &relative_position))
+        .or_else(|| health_modifier_hover(skill_line, &relative_position))
+        .or_else(|| attribute_hover(skill_line, &relative_position))?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: contents,
+        }),
+        range: Some(hover_range.relative_to(&range.start).to_range()),
+    })
+}
+
+/// Shows the keys and list indices leading to the node at a position, e.g. `MyMob > Skills > [0]`.
+fn yaml_path_hover(doc: &DocumentInfo, position: &CustomPosition) -> Option<Hover> {
+    let path = node_path(doc.yaml.as_ref()?, position, &doc.source);
+    if path.is_empty() {
+        return None;
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{}`", path.join(" > ")),
+        }),
+        range: None,
+    })
+}
+
+/// Describes the mechanic whose name is at the given position, along with the name's range.
+/// Aliases like `d` are described under their canonical name, `damage`.
+/// The position is relative to the skill line. Returns `None` for unknown mechanics.
+fn mechanic_hover(
+    skill_line: &SkillLine,
+    position: &CustomPosition,
+) -> Option<(String, CustomRange)> {
+    let path = skill_line.path_at(position);
+    let mechanic_expr = path.iter().rev().find_map(|node| match node {
+        NodeRef::Mechanic(mechanic) => Some(*mechanic),
+        _ => None,
+    })?;
+    let name_range = mechanic_expr.name().get_range()?;
+    if *position < name_range.start || name_range.end < *position {
+        return None;
+    }
+    let mechanic = mechanic(&mechanic_expr.name().value())?;
+    Some((describe_mechanic(mechanic), name_range))
+}
+
+fn describe_mechanic(mechanic: &Mechanic) -> String {
+    let mut lines = vec![format!("**{}** (mechanic)", mechanic.names[0])];
+    lines.extend(mechanic.description.clone());
+    if let Some(replacement) = &mechanic.deprecated {
+        lines.push(format!("Deprecated, use `{}` instead.", replacement));
+    }
+    if mechanic.requires_targeter {
+        lines.push(String::from("Requires a targeter."));
+    }
+    if mechanic.names.len() > 1 {
+        let aliases = mechanic.names[1..]
+            .iter()
+            .map(|alias| format!("`{}`", alias))
+            .collect::<Vec<_>>();
+        lines.push(format!("Aliases: {}", aliases.join(", ")));
+    }
+    lines.join("\n\n")
+}
+
+/// Describes the targeter whose name is at the given position, e.g. `PIR` in `@PIR{r=5}`,
+/// along with the range from its `@` to the end of its name.
+/// The position is relative to the skill line. Returns `None` for unknown targeters.
+fn targeter_hover(
+    skill_line: &SkillLine,
+    position: &CustomPosition,
+) -> Option<(String, CustomRange)> {
+    let path = skill_line.path_at(position);
+    let targeter_expr = path.iter().rev().find_map(|node| match node {
+        NodeRef::Targeter(targeter) => Some(*targeter),
+        _ => None,
+    })?;
+    let name = match targeter_expr.name() {
+        TargeterName::Identifier(name) => name,
+        TargeterName::Placeholder(_) => return None,
+    };
+    let name_range = CustomRange::new(targeter_expr.at().get_range().start, name.get_range().end);
+    if *position < name_range.start || name_range.end < *position {
+        return None;
+    }
+    let targeter = targeter(name.lexeme.as_deref()?)?;
+    Some((describe_targeter(targeter), name_range))
+}
+
+fn describe_targeter(targeter: &TargeterInfo) -> String {
+    let mut lines = vec![format!("**@{}** (targeter)", targeter.names[0])];
+    lines.extend(targeter.description.clone());
+    if targeter.names.len() > 1 {
+        let aliases = targeter.names[1..]
+            .iter()
+            .map(|alias| format!("`@{}`", alias))
+            .collect::<Vec<_>>();
+        lines.push(format!("Aliases: {}", aliases.join(", ")));
+    }
+    lines.join("\n\n")
+}
+
+/// Explains the health modifier at the given position, e.g. `<50%`, along with its range.
+/// The position is relative to the skill line.
+fn health_modifier_hover(
+    skill_line: &SkillLine,
+    position: &CustomPosition,
+) -> Option<(String, CustomRange)> {
+    let path = skill_line.path_at(position);
+    let modifier = path.iter().rev().find_map(|node| match node {
+        NodeRef::HealthModifier(modifier) => Some(*modifier),
+        _ => None,
+    })?;
+    Some((describe_health_modifier(modifier), modifier.get_range()))
+}
+
+fn describe_health_modifier(modifier: &HealthModifier) -> String {
+    let bounds = match modifier.value() {
+        HealthModifierValueOrRange::Value(value) => vec![value],
+        HealthModifierValueOrRange::Range(min, max) => vec![min, max],
+    };
+    let is_percentage =
+        |value: &&HealthModifierValue| matches!(value, HealthModifierValue::Percentage(..));
+    // mention the maximum health once if every bound is a percentage, or after each one that is
+    let all_percentages = bounds.iter().all(is_percentage);
+    let bounds = bounds
+        .iter()
+        .map(|value| {
+            if is_percentage(value) && !all_percentages {
+                format!("`{}` of its maximum health", value.to_source())
+            } else {
+                format!("`{}`", value.to_source())
+            }
+        })
+        .collect::<Vec<_>>();
+    let comparison = match (modifier.value(), modifier.operator().type_) {
+        (HealthModifierValueOrRange::Range(..), _) => "between",
+        (_, TokenType::LessThan) => "below",
+        (_, TokenType::LessEqual) => "at most",
+        (_, TokenType::GreaterThan) => "above",
+        (_, TokenType::GreaterEqual) => "at least",
+        _ => "exactly",
+    };
+    format!(
+        "**Health modifier**\n\nExecutes when the caster's health is {} {}{}.",
+        comparison,
+        bounds.join(" and "),
+        if all_percentages {
+            " of its maximum health"
+        } else {
+            ""
+        }
+    )
+}
+
+/// Describes the mechanic attribute whose key is at the given position, e.g. `amount` in
+/// `damage{amount=5}`, along with the key's range. The position is relative to the skill line.
+/// Returns `None` for unknown mechanics and attributes.
+fn attribute_hover(
+    skill_line: &SkillLine,
+    position: &CustomPosition,
+) -> Option<(String, CustomRange)> {
+    let path = skill_line.path_at(position);
+    // the innermost MLC of a mechanic, as skill lines can be nested in inline skills
+    let (mechanic_expr, mlc) = path.windows(3).rev().find_map(|nodes| match nodes {
+        [NodeRef::Mechanic(mechanic), NodeRef::MlcContainer(_), NodeRef::Mlc(mlc)] => {
+            Some((*mechanic, *mlc))
+        }
+        _ => None,
+    })?;
+    let key_range = mlc.key().get_range();
+    if *position < key_range.start || key_range.end < *position {
+        return None;
+    }
+    let mechanic = mechanic(&mechanic_expr.name().value())?;
+    let attribute = mechanic.attribute(&mlc.key_name())?;
+    Some((describe_attribute(&mechanic.names[0], attribute), key_range))
+}
+
+fn describe_attribute(mechanic: &str, attribute: &Attribute) -> String {
+    let mut lines = vec![
+        format!("**{}** ({} attribute)", attribute.names[0], mechanic),
+        format!("Type: {}", attribute.type_.description()),
+    ];
+    if let Some(default) = &attribute.default {
+        lines.push(format!("Default: `{}`", default));
+    }
+    if attribute.names.len() > 1 {
+        let aliases = attribute.names[1..]
+            .iter()
+            .map(|alias| format!("`{}`", alias))
+            .collect::<Vec<_>>();
+        lines.push(format!("Aliases: {}", aliases.join(", ")));
+    }
+    lines.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mythic_parser::parse_skill_line, testing::TestServer};
+
+    use super::*;
+
+    #[test]
+    fn describes_the_attribute_of_an_mlc_key() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=5} @target\n"),
+        );
+        let hover = hover(&doc, &CustomPosition::new(3, 13)).unwrap();
+        match hover.contents {
+            HoverContents::Markup(markup) => {
+                assert_eq!(markup.kind, MarkupKind::Markdown);
+                assert!(markup.value.contains("Type: a number"), "{}", markup.value);
+            }
+            contents => panic!("expected markdown, got {:?}", contents),
+        }
+    }
+
+    #[test]
+    fn skips_unknown_attributes() {
+        let (skill_line, _) = parse_skill_line("damage{unknown=5} @target").unwrap();
+        assert!(attribute_hover(&skill_line, &CustomPosition::new(0, 9)).is_none());
+    }
+
+    #[test]
+    fn shows_the_canonical_name_of_an_alias() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Type: ZOMBIE\n  Skills:\n  - d{a=1} @target\n"),
+        );
+        assert!(doc.diagnostics.is_empty());
+        let hover = hover(&doc, &CustomPosition::new(3, 4)).unwrap();
+        match hover.contents {
+            HoverContents::Markup(markup) => {
+                assert!(markup.value.contains("damage"), "{}", markup.value)
+            }
+            contents => panic!("expected markdown, got {:?}", contents),
+        }
+    }
+
+    #[test]
+    fn explains_health_modifier_ranges() {
+        let (skill_line, _) = parse_skill_line("damage{amount=5} @self =50-100").unwrap();
+        let (contents, range) =
+            health_modifier_hover(&skill_line, &CustomPosition::new(0, 25)).unwrap();
+        assert!(
+            contents.contains("`50`") && contents.contains("`100`"),
+            "{}",
+            contents
+        );
+        assert_eq!(range.start.character, 23);
+    }
+
+    #[test]
+    fn describes_attributes_ignoring_case() {
+        let (skill_line, _) = parse_skill_line("damage{AMOUNT=5} @target").unwrap();
+        let (contents, range) = attribute_hover(&skill_line, &CustomPosition::new(0, 9)).unwrap();
+        // the canonical name is shown, whatever the casing in the skill line
+        assert!(contents.starts_with("**amount**"), "{}", contents);
+        assert_eq!((range.start.character, range.end.character), (7, 13));
+    }
+
+    #[test]
+    fn shows_the_yaml_path_without_a_richer_hover() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("MyMob:\n  Type: ZOMBIE\n  Options:\n    MovementSpeed: 0.2\n"),
+        );
+        let hover = hover(&doc, &CustomPosition::new(3, 20)).unwrap();
+        match hover.contents {
+            HoverContents::Markup(markup) => {
+                assert_eq!(markup.value, "`MyMob > Options > MovementSpeed`")
+            }
+            contents => panic!("expected markdown, got {:?}", contents),
+        }
+    }
+}
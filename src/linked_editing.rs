@@ -0,0 +1,48 @@
+use tower_lsp::lsp_types::LinkedEditingRanges;
+
+use crate::{documents::DocumentInfo, utilities::positions_and_ranges::CustomPosition};
+
+/// Returns the innermost pair of delimiters around a position in a document, i.e. the `{}` of an
+/// MLC, the `[]` of an inline skill, or the `<>` of a placeholder, so they're edited together.
+pub fn linked_editing_ranges(
+    doc: &DocumentInfo,
+    position: &CustomPosition,
+) -> Option<LinkedEditingRanges> {
+    let (range, skill_line) = doc.skill_at(position)?;
+    let relative_position = position.relative_from(&range.start);
+    let (open, close) = skill_line
+        .path_at(&relative_position)
+        .iter()
+        .rev()
+        .find_map(|node| node.delimiters())?;
+    Some(LinkedEditingRanges {
+        ranges: vec![
+            open.get_range().relative_to(&range.start).to_range(),
+            close.get_range().relative_to(&range.start).to_range(),
+        ],
+        word_pattern: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestServer;
+
+    #[test]
+    fn links_the_braces_around_the_cursor() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Skills:\n  - damage{amount=5} @self\n"),
+        );
+        let ranges = linked_editing_ranges(&doc, &CustomPosition::new(2, 14))
+            .unwrap()
+            .ranges;
+        let positions = ranges
+            .iter()
+            .map(|range| (range.start.line, range.start.character, range.end.character))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(2, 10, 11), (2, 19, 20)]);
+    }
+}
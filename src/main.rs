@@ -1,32 +1,60 @@
+mod code_actions;
+mod commands;
+mod completion;
 mod documents;
 mod errors;
+mod formatting;
+mod hover;
+mod linked_editing;
+mod matching_bracket;
 mod mythic_parser;
+mod pull_diagnostics;
+mod settings;
+mod skill_index;
+#[cfg(test)]
+mod testing;
 mod utilities;
+mod workspace;
 mod yaml;
-use core::{marker::Send, pin::Pin};
-use std::mem::take;
+use std::{
+    mem::take,
+    path::PathBuf,
+    sync::{Mutex, RwLock},
+};
 
-use chumsky::primitive::Container;
-use dashmap::DashMap;
-use documents::{DocumentInfo, LEGEND_TYPE};
-use errors::error_registry::Error;
+use dashmap::{DashMap, DashSet};
+use documents::{DocumentInfo, LEGEND_MODIFIER, LEGEND_TYPE};
 use ropey::Rope;
-use tokio::{io::AsyncWriteExt, join};
+use serde_json::Value;
+use settings::{LogLevel, Settings};
+use tokio::task::JoinSet;
 use tower_lsp::{
     jsonrpc::Result,
     lsp_types::{
-        DidChangeTextDocumentParams, DidOpenTextDocumentParams, DocumentFilter, InitializeParams,
-        InitializeResult, InitializedParams, MessageType, SemanticToken, SemanticTokens,
-        SemanticTokensClientCapabilities, SemanticTokensFullOptions, SemanticTokensLegend,
-        SemanticTokensOptions, SemanticTokensParams, SemanticTokensRegistrationOptions,
-        SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
-        StaticRegistrationOptions, TextDocumentItem, TextDocumentRegistrationOptions,
-        TextDocumentSyncCapability, TextDocumentSyncKind, WorkDoneProgressOptions,
+        CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+        CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+        CallHierarchyServerCapability, CodeActionParams, CodeActionProviderCapability,
+        CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams, Command, CompletionOptions,
+        CompletionParams, CompletionResponse, Diagnostic, DiagnosticServerCapabilities,
+        DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+        DidChangeWatchedFilesRegistrationOptions, DidCloseTextDocumentParams,
+        DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentFilter,
+        DocumentFormattingParams, DocumentLink, DocumentLinkOptions, DocumentLinkParams,
+        ExecuteCommandOptions, ExecuteCommandParams, FileChangeType, FileSystemWatcher,
+        GlobPattern, Hover, HoverParams, HoverProviderCapability, InitializeParams,
+        InitializeResult, InitializedParams, LinkedEditingRangeParams,
+        LinkedEditingRangeServerCapabilities, LinkedEditingRanges, MessageType, OneOf,
+        PrepareRenameResponse, Registration, RenameOptions, RenameParams, SaveOptions,
+        SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+        SemanticTokensParams, SemanticTokensRegistrationOptions, SemanticTokensResult,
+        SemanticTokensServerCapabilities, ServerCapabilities, StaticRegistrationOptions,
+        TextDocumentItem, TextDocumentPositionParams, TextDocumentRegistrationOptions,
+        TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+        TextDocumentSyncSaveOptions, TextEdit, Url, WorkDoneProgressOptions, WorkspaceEdit,
     },
     Client, LanguageServer, LspService, Server,
 };
 use utilities::positions_and_ranges::{CustomPosition, CustomRange};
-use yaml_rust::YamlLoader;
 
 #[derive(Debug)]
 pub struct Backend {
@@ -34,21 +62,51 @@ pub struct Backend {
     client: Client,
     /// A map of cached document information.
     document_map: DashMap<String, DocumentInfo>,
+    /// The documents the client has open. Their contents come from the client, not the disk.
+    open_documents: DashSet<String>,
+    /// The settings sent by the client when initializing.
+    settings: RwLock<Settings>,
+    /// The directories of the workspace folders, whose files are preloaded once initialized.
+    workspace_roots: RwLock<Vec<PathBuf>>,
+    /// The tasks spawned by [`Self::log`], which are awaited on shutdown so no message is lost.
+    log_tasks: Mutex<JoinSet<()>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let settings =
+            Settings::from_initialization_options(params.initialization_options.as_ref());
+        let trigger_characters = settings.completion.trigger_characters();
+        *self.settings.write().unwrap() = settings;
+        let roots = match params.workspace_folders {
+            Some(folders) => folders.into_iter().map(|folder| folder.uri).collect(),
+            None => params.root_uri.into_iter().collect::<Vec<_>>(),
+        };
+        *self.workspace_roots.write().unwrap() = roots
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect();
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
                 position_encoding: None,
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(false),
+                        })),
+                        ..TextDocumentSyncOptions::default()
+                    },
                 )),
                 selection_range_provider: None,
-                hover_provider: None,
-                completion_provider: None,
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(trigger_characters),
+                    ..CompletionOptions::default()
+                }),
                 signature_help_provider: None,
                 definition_provider: None,
                 type_definition_provider: None,
@@ -57,19 +115,33 @@ impl LanguageServer for Backend {
                 document_highlight_provider: None,
                 document_symbol_provider: None,
                 workspace_symbol_provider: None,
-                code_action_provider: None,
-                code_lens_provider: None,
-                document_formatting_provider: None,
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                document_formatting_provider: Some(OneOf::Left(true)),
                 document_range_formatting_provider: None,
                 document_on_type_formatting_provider: None,
-                rename_provider: None,
-                document_link_provider: None,
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
                 color_provider: None,
                 folding_range_provider: None,
                 declaration_provider: None,
-                execute_command_provider: None,
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: commands::COMMANDS
+                        .iter()
+                        .map(|command| command.to_string())
+                        .collect(),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
                 workspace: None,
-                call_hierarchy_provider: None,
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
                         SemanticTokensRegistrationOptions {
@@ -86,7 +158,7 @@ impl LanguageServer for Backend {
                                 work_done_progress_options: WorkDoneProgressOptions::default(),
                                 legend: SemanticTokensLegend {
                                     token_types: LEGEND_TYPE.into(),
-                                    token_modifiers: vec![],
+                                    token_modifiers: LEGEND_MODIFIER.into(),
                                 },
                                 range: Some(false),
                                 full: Some(SemanticTokensFullOptions::Bool(true)),
@@ -98,97 +170,368 @@ impl LanguageServer for Backend {
                 moniker_provider: None,
                 inline_value_provider: None,
                 inlay_hint_provider: None,
-                linked_editing_range_provider: None,
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(
+                    true,
+                )),
+                // clients that don't pull diagnostics still get them pushed
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    pull_diagnostics::options(),
+                )),
                 experimental: None,
             },
             offset_encoding: None,
         })
     }
     async fn initialized(&self, _: InitializedParams) {
-        self.client
-            .log_message(MessageType::INFO, "initialized!")
+        self.log_async(MessageType::INFO, String::from("initialized!"))
+            .await;
+        // watch files that aren't open, so metaskills defined in them stay up to date
+        let registration = Registration {
+            id: String::from("mythic-watched-files"),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(String::from("**/*.{yml,yaml}")),
+                    kind: None,
+                }],
+            })
+            .ok(),
+        };
+        if let Err(error) = self.client.register_capability(vec![registration]).await {
+            self.log_async(
+                MessageType::WARNING,
+                format!("Failed to watch files: {}", error),
+            )
             .await;
+        }
+        let roots = self.workspace_roots.read().unwrap().clone();
+        self.preload_workspace(&roots).await;
     }
 
     async fn shutdown(&self) -> Result<()> {
-        self.client
-            .log_message(MessageType::INFO, "shutting down!")
+        self.log_async(MessageType::INFO, String::from("shutting down!"))
             .await;
+        // take the tasks out first, as the lock can't be held across `.await`
+        let mut log_tasks = take(&mut *self.log_tasks.lock().unwrap());
+        while log_tasks.join_next().await.is_some() {}
+        self.document_map.clear();
+        self.open_documents.clear();
         Ok(())
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file opened!")
+        self.log_async(MessageType::INFO, String::from("file opened!"))
             .await;
+        self.open_documents
+            .insert(params.text_document.uri.to_string());
         self.on_change(params.text_document).await
     }
 
+    /// Keeps the closed document cached, as other documents may use the metaskills it defines.
+    /// From now on, it's kept up to date with the disk instead.
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.open_documents
+            .remove(&params.text_document.uri.to_string());
+    }
+
     async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        // some clients send no changes at all, e.g. when saving an unchanged document
+        let change = match params.content_changes.pop() {
+            Some(change) => change,
+            None => return,
+        };
         self.on_change(TextDocumentItem {
             uri: params.text_document.uri,
-            text: take(&mut params.content_changes[0].text),
+            text: change.text,
             version: params.text_document.version,
             language_id: String::from("yaml"),
         })
         .await
     }
 
+    /// Runs the checks that are too slow for every change, like resolving metaskill references
+    /// across the workspace, and publishes their diagnostics along with the others.
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let doc_info = match self.save_document(&uri) {
+            Some(doc_info) => doc_info,
+            None => return,
+        };
+        // the client asks for the diagnostics itself instead
+        if self.settings.read().unwrap().pull_diagnostics {
+            return;
+        }
+        self.client
+            .publish_diagnostics(uri, doc_info.diagnostics, None)
+            .await
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            // the client's contents are newer than the disk's, and it tells us about them itself
+            if self.open_documents.contains(&change.uri.to_string()) {
+                continue;
+            }
+            if change.typ == FileChangeType::DELETED {
+                self.document_map.remove(&change.uri.to_string());
+                // otherwise the client keeps showing the deleted file's last diagnostics
+                if !self.settings.read().unwrap().pull_diagnostics {
+                    self.client
+                        .publish_diagnostics(change.uri, Vec::new(), None)
+                        .await;
+                }
+                continue;
+            }
+            let path = match change.uri.to_file_path() {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            match tokio::fs::read_to_string(&path).await {
+                Ok(text) => {
+                    let doc_info = self.update_document(&change.uri, text);
+                    // replace what was published for the previous contents
+                    if !self.settings.read().unwrap().pull_diagnostics {
+                        self.client
+                            .publish_diagnostics(change.uri, doc_info.diagnostics, None)
+                            .await;
+                    }
+                }
+                Err(error) => {
+                    self.log_async(
+                        MessageType::WARNING,
+                        format!("Failed to read {}: {}", path.display(), error),
+                    )
+                    .await
+                }
+            }
+        }
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        self.client
-            .log_message(MessageType::INFO, "semantic tokens!")
-            .await;
-        let doc_info = self.document_map.get(&params.text_document.uri.to_string());
-
-        if doc_info.is_none() {
-            return Ok(None);
-        }
-        let doc_info = doc_info.unwrap();
-
-        let doc_info = doc_info.value();
-        let tokens = &mut doc_info.semantic_tokens.clone();
-
-        let semantic_tokens = (|| {
-            let rope = &doc_info.source;
-            tokens.sort_by(|a, b| a.start.cmp(&b.start));
-            let mut pre_line = 0;
-            let mut pre_start = 0;
-            let semantic_tokens = tokens
-                .iter()
-                .filter_map(|token| {
-                    let line = rope.try_byte_to_line(token.start).ok()? as u32;
-                    let first = rope.try_line_to_char(line as usize).ok()? as u32;
-                    let start = rope.try_byte_to_char(token.start).ok()? as u32 - first;
-                    let delta_line = line - pre_line;
-                    let delta_start = if delta_line == 0 {
-                        start - pre_start
-                    } else {
-                        start
-                    };
-                    let ret = Some(SemanticToken {
-                        delta_line,
-                        delta_start,
-                        length: token.length as u32,
-                        token_type: token.token_type,
-                        token_modifiers_bitset: 0,
-                    });
-                    pre_line = line;
-                    pre_start = start;
-                    ret
-                })
-                .collect::<Vec<_>>();
-            Some(semantic_tokens)
-        })();
-        if let Some(semantic_token) = semantic_tokens {
-            return Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-                result_id: None,
-                data: semantic_token,
-            })));
-        }
-        Ok(None)
+        self.log_async(MessageType::INFO, String::from("semantic tokens!"))
+            .await;
+        // encoded when the document was parsed, so this doesn't redo it on every request.
+        // The tokens are copied out right away, so the map isn't locked while responding.
+        let data = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info.encoded_semantic_tokens.clone(),
+            None => return Ok(None),
+        };
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let params = params.text_document_position_params;
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        let position = CustomPosition::from_position(&params.position);
+        Ok(hover::hover(&doc_info, &position))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let params = params.text_document_position;
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        let position = CustomPosition::from_position(&params.position);
+        let items = completion::completions(&doc_info.source, &position);
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let params = params.text_document_position_params;
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        let position = CustomPosition::from_position(&params.position);
+        Ok(linked_editing::linked_editing_ranges(&doc_info, &position))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        Ok(Some(formatting::format_document(&doc_info)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        let range = CustomRange::new(
+            CustomPosition::from_position(&params.range.start),
+            CustomPosition::from_position(&params.range.end),
+        );
+        Ok(Some(code_actions::code_actions(&doc_info, &range)))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        // Clone the definitions out so the map isn't borrowed while searching other documents.
+        let definitions = match self.document_map.get(&uri.to_string()) {
+            Some(doc_info) => doc_info.skill_definitions.clone(),
+            None => return Ok(None),
+        };
+
+        let lenses = definitions
+            .into_iter()
+            .map(|definition| {
+                let references = self.find_skill_references(&definition.name);
+                let title = match references.len() {
+                    1 => String::from("1 reference"),
+                    count => format!("{} references", count),
+                };
+                let range = definition.range.to_range();
+                CodeLens {
+                    range,
+                    command: Some(Command {
+                        title,
+                        command: String::from("mythic.showReferences"),
+                        arguments: Some(vec![
+                            serde_json::json!(uri),
+                            serde_json::json!(range.start),
+                            serde_json::json!(references),
+                        ]),
+                    }),
+                    data: None,
+                }
+            })
+            .collect();
+        Ok(Some(lenses))
+    }
+
+    /// Links every metaskill reference in a document. The targets are only looked up
+    /// in [`Self::document_link_resolve`], as the definitions may be in other documents.
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let doc_info = match self.document_map.get(&params.text_document.uri.to_string()) {
+            Some(doc_info) => doc_info,
+            None => return Ok(None),
+        };
+        let links = doc_info
+            .skill_references
+            .iter()
+            .map(|reference| DocumentLink {
+                range: reference.range.to_range(),
+                target: None,
+                tooltip: Some(format!("Go to {}", reference.name)),
+                data: Some(serde_json::json!(reference.name)),
+            })
+            .collect();
+        Ok(Some(links))
+    }
+
+    async fn document_link_resolve(&self, mut link: DocumentLink) -> Result<DocumentLink> {
+        if let Some(name) = link.data.as_ref().and_then(|data| data.as_str()) {
+            link.target = self.skill_definition_link(name);
+        }
+        Ok(link)
+    }
+
+    /// Only metaskill names can be renamed, so this rejects any other position up front.
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let position = CustomPosition::from_position(&params.position);
+        Ok(self
+            .skill_symbol_at(&params.text_document.uri, &position)
+            .map(|(_, range)| PrepareRenameResponse::Range(range.to_range())))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = CustomPosition::from_position(&params.text_document_position.position);
+        let name = match self.skill_name_at(&uri, &position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        Ok(Some(self.rename_skill(&name, &params.new_name)))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let params = params.text_document_position_params;
+        let position = CustomPosition::from_position(&params.position);
+        let name = match self.skill_name_at(&params.text_document.uri, &position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        Ok(self
+            .find_skill_definition(&name)
+            .map(|(uri, definition)| vec![definition.to_call_hierarchy_item(uri)]))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let mut incoming_calls: Vec<CallHierarchyIncomingCall> = Vec::new();
+        for call in self.find_skill_calls() {
+            if call.callee.name != params.item.name {
+                continue;
+            }
+            let range = call.callee.range.to_range();
+            match incoming_calls
+                .iter_mut()
+                .find(|incoming| incoming.from.name == call.caller.name)
+            {
+                Some(incoming) => incoming.from_ranges.push(range),
+                None => incoming_calls.push(CallHierarchyIncomingCall {
+                    from: call.caller.to_call_hierarchy_item(call.caller_uri),
+                    from_ranges: vec![range],
+                }),
+            }
+        }
+        Ok(Some(incoming_calls))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let mut outgoing_calls: Vec<CallHierarchyOutgoingCall> = Vec::new();
+        for call in self.find_skill_calls() {
+            if call.caller.name != params.item.name {
+                continue;
+            }
+            let range = call.callee.range.to_range();
+            match outgoing_calls
+                .iter_mut()
+                .find(|outgoing| outgoing.to.name == call.callee.name)
+            {
+                Some(outgoing) => outgoing.from_ranges.push(range),
+                None => {
+                    // calls to metaskills that aren't defined anywhere can't be shown
+                    if let Some((uri, definition)) = self.find_skill_definition(&call.callee.name) {
+                        outgoing_calls.push(CallHierarchyOutgoingCall {
+                            to: definition.to_call_hierarchy_item(uri),
+                            from_ranges: vec![range],
+                        });
+                    }
+                }
+            }
+        }
+        Ok(Some(outgoing_calls))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        commands::execute(self, &params.command, &params.arguments)
     }
 }
 
@@ -197,23 +540,27 @@ impl Backend {
         Self {
             client,
             document_map: DashMap::new(),
+            open_documents: DashSet::new(),
+            settings: RwLock::new(Settings::default()),
+            workspace_roots: RwLock::new(Vec::new()),
+            log_tasks: Mutex::new(JoinSet::new()),
         }
     }
     pub async fn on_change(&self, params: TextDocumentItem) {
-        self.client
-            .log_message(MessageType::INFO, "file changed!")
+        self.log_async(MessageType::INFO, String::from("file changed!"))
             .await;
-        let mut doc_info = DocumentInfo::new(Rope::from(params.text), None);
-        yaml::parser::parse(&self, &mut doc_info);
-
-        self.document_map
-            .insert(params.uri.to_string(), doc_info.clone());
+        let doc_info = self.update_document(&params.uri, params.text);
 
         // Log the diagnostics to the console.
-        self.client
-            .log_message(MessageType::INFO, format!("{:?}", &doc_info.diagnostics))
+        self.log_async(MessageType::INFO, format!("{:?}", &doc_info.diagnostics))
             .await;
 
+        // the client asks for the diagnostics itself instead
+        let pull_diagnostics = self.settings.read().unwrap().pull_diagnostics;
+        if pull_diagnostics {
+            return;
+        }
+
         self.client
             .publish_diagnostics(
                 params.uri,
@@ -222,14 +569,76 @@ impl Backend {
             )
             .await
     }
+    /// Parses a new version of a document and stores it in the document map, returning it.
+    /// Unlike [`Self::on_change`], this doesn't talk to the client, so it can also be used
+    /// to drive the server with in-memory documents.
+    /// Every parse starts from a fresh [`DocumentInfo`], so the diagnostics replace the previous
+    /// ones instead of adding to them.
+    pub fn update_document(&self, uri: &Url, text: String) -> DocumentInfo {
+        self.parse_document(uri, text, Vec::new())
+    }
+    /// Parses a saved document again, adding the diagnostics that are only checked on save,
+    /// like unresolved metaskill references. Returns `None` if the document isn't known.
+    pub fn save_document(&self, uri: &Url) -> Option<DocumentInfo> {
+        let text = self.document_map.get(&uri.to_string())?.source.to_string();
+        // the stored version has the same text, so it has the same references
+        let diagnostics = self.unresolved_skill_references(uri);
+        Some(self.parse_document(uri, text, diagnostics))
+    }
+    /// Parses a document like [`Self::update_document`], adding `extra_diagnostics` before the
+    /// settings, like the severity overrides and the diagnostic limit, are applied.
+    fn parse_document(
+        &self,
+        uri: &Url,
+        text: String,
+        extra_diagnostics: Vec<Diagnostic>,
+    ) -> DocumentInfo {
+        let mut doc_info = DocumentInfo::new(uri.clone(), Rope::from(text), None);
+        if let Some(previous) = self.document_map.get(&uri.to_string()) {
+            doc_info.reuse_skill_scalars(&previous);
+        }
+        yaml::parser::parse(self, &mut doc_info);
+        // whatever wasn't reused is out of date
+        doc_info.reusable_skill_scalars.clear();
+        doc_info.encoded_semantic_tokens = doc_info.encode_semantic_tokens();
+        for diagnostic in extra_diagnostics {
+            doc_info.push_diagnostic_dedup(diagnostic);
+        }
+        {
+            let settings = self.settings.read().unwrap();
+            doc_info.apply_severity_overrides(&settings.severity_overrides);
+            if settings.legacy_diagnostic_codes {
+                doc_info.use_legacy_diagnostic_codes();
+            }
+            if let Some(max_diagnostics) = settings.max_diagnostics {
+                doc_info.limit_diagnostics(max_diagnostics);
+            }
+        }
+
+        self.document_map.insert(uri.to_string(), doc_info.clone());
+        doc_info
+    }
+    /// Whether messages of the given type pass the configured log level, see [`Settings::log_level`].
+    pub fn should_log(&self, message_type: MessageType) -> bool {
+        LogLevel::of(message_type) <= self.settings.read().unwrap().log_level()
+    }
     /// Logs a message to the client in a separate async task.
     pub fn log(&self, message_type: MessageType, message: String) {
+        if !self.should_log(message_type) {
+            return;
+        }
         let client = self.client.clone();
-        tokio::spawn(async move {
+        let mut log_tasks = self.log_tasks.lock().unwrap();
+        // forget the tasks that are already done, so the set doesn't grow forever
+        while log_tasks.try_join_next().is_some() {}
+        log_tasks.spawn(async move {
             client.log_message(message_type, message).await;
         });
     }
     pub async fn log_async(&self, message_type: MessageType, message: String) {
+        if !self.should_log(message_type) {
+            return;
+        }
         let client = self.client.clone();
         client.log_message(message_type, message).await;
     }
@@ -238,17 +647,494 @@ impl Backend {
 #[tokio::main]
 async fn main() {
     env_logger::init();
+    // load the catalog up front, so a broken one fails at startup rather than on the first request
+    mythic_parser::catalog::catalog();
 
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::build(Backend::new).finish();
+    let (service, socket) = LspService::build(Backend::new)
+        .custom_method(pull_diagnostics::METHOD, Backend::document_diagnostic)
+        .finish();
 
     service
         .inner()
-        .client
-        .log_message(MessageType::INFO, "Starting server...")
+        .log_async(MessageType::INFO, String::from("Starting server..."))
         .await;
 
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{
+        DiagnosticSeverity, FileEvent, Position, Range, TextDocumentIdentifier,
+        VersionedTextDocumentIdentifier,
+    };
+
+    use super::*;
+    use crate::testing::TestServer;
+
+    #[tokio::test]
+    async fn opening_a_document_stores_its_diagnostics() {
+        let mut server = TestServer::new();
+        server.initialize(serde_json::Value::Null).await;
+        let uri = server
+            .open("mobs.yml", "Mob:\n  Skills:\n  - damage{amount=1 @self\n")
+            .await;
+        let diagnostics = server.document(&uri).diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(server.published_diagnostics(&uri), Some(diagnostics));
+    }
+
+    #[tokio::test]
+    async fn code_lens_counts_references() {
+        let server = TestServer::new();
+        let uri = server
+            .open(
+                "skills.yml",
+                "Boom:\n  Skills:\n  - damage{amount=1} @self\nMob:\n  Type: ZOMBIE\n  Skills:\n  \
+                 - skill{s=Boom}\n  - skill{s=Boom} ~onAttack\n",
+            )
+            .await;
+        let lenses = server
+            .backend()
+            .code_lens(CodeLensParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lenses.len(), 1);
+        assert_eq!(lenses[0].command.as_ref().unwrap().title, "2 references");
+    }
+
+    #[tokio::test]
+    async fn call_hierarchy_links_calling_metaskills() {
+        let server = TestServer::new();
+        let uri = server
+            .open(
+                "skills.yml",
+                "A:\n  Skills:\n  - skill{s=B}\nB:\n  Skills:\n  - damage{amount=1}\n",
+            )
+            .await;
+        let backend = server.backend();
+        let prepare = |line, character| CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                Position::new(line, character),
+            ),
+            work_done_progress_params: Default::default(),
+        };
+        let a = backend
+            .prepare_call_hierarchy(prepare(0, 0))
+            .await
+            .unwrap()
+            .unwrap();
+        let b = backend
+            .prepare_call_hierarchy(prepare(3, 0))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((a[0].name.as_str(), b[0].name.as_str()), ("A", "B"));
+
+        let outgoing = backend
+            .outgoing_calls(CallHierarchyOutgoingCallsParams {
+                item: a[0].clone(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let outgoing = outgoing
+            .iter()
+            .map(|call| call.to.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(outgoing, vec!["B"]);
+
+        let incoming = backend
+            .incoming_calls(CallHierarchyIncomingCallsParams {
+                item: b[0].clone(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let incoming = incoming
+            .iter()
+            .map(|call| call.from.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(incoming, vec!["A"]);
+    }
+
+    #[tokio::test]
+    async fn limits_the_published_diagnostics() {
+        let mut server = TestServer::new();
+        server
+            .initialize(serde_json::json!({ "maxDiagnostics": 2 }))
+            .await;
+        let uri = server
+            .open(
+                "mobs.yml",
+                "Mob:\n  Skills:\n  - damage{amount=1;amount=2} @self\n  \
+                 - damage{amount=1;amount=2} @self\n  - damage{amount=1;amount=2} @self\n",
+            )
+            .await;
+        let diagnostics = server.published_diagnostics(&uri).unwrap();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[2].message, "1 more diagnostic suppressed.");
+        assert_eq!(diagnostics[2].range.start.line, 4);
+    }
+
+    /// Creates an empty directory for a test's files on disk.
+    fn test_directory(name: &str) -> PathBuf {
+        let name = format!("mythic-{}-{}", name, std::process::id());
+        let directory = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[tokio::test]
+    async fn created_files_resolve_references() {
+        let server = TestServer::new();
+        let directory = test_directory("created-files");
+        let uri = server
+            .open(
+                "mobs.yml",
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - skill{s=Boom}\n",
+            )
+            .await;
+        assert_eq!(server.backend().unresolved_skill_references(&uri).len(), 1);
+
+        let path = directory.join("skills.yml");
+        std::fs::write(&path, "Boom:\n  Skills:\n  - damage{amount=1}\n").unwrap();
+        server
+            .backend()
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent::new(
+                    Url::from_file_path(&path).unwrap(),
+                    FileChangeType::CREATED,
+                )],
+            })
+            .await;
+        assert!(server
+            .backend()
+            .unresolved_skill_references(&uri)
+            .is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn changed_files_keep_open_documents() {
+        let server = TestServer::new();
+        let directory = test_directory("open-files");
+        let path = directory.join("skills.yml");
+        std::fs::write(&path, "Boom: {}\n").unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        server
+            .backend()
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    String::from("yaml"),
+                    0,
+                    String::from("Unsaved: {}\n"),
+                ),
+            })
+            .await;
+        server
+            .backend()
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent::new(uri.clone(), FileChangeType::CHANGED)],
+            })
+            .await;
+        assert_eq!(server.document(&uri).source.to_string(), "Unsaved: {}\n");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pulled_diagnostics_match_the_pushed_ones() {
+        let mut server = TestServer::new();
+        let result = server.initialize(serde_json::Value::Null).await;
+        assert!(result.capabilities.diagnostic_provider.is_some());
+        let uri = server
+            .open("mobs.yml", "Mob:\n  Skills:\n  - damage{amount=1 @self\n")
+            .await;
+        let pushed = server.published_diagnostics(&uri).unwrap();
+        assert_eq!(pushed.len(), 1);
+        let report = server
+            .backend()
+            .document_diagnostic(pull_diagnostics::DocumentDiagnosticParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                previous_result_id: None,
+            })
+            .await
+            .unwrap();
+        match report {
+            pull_diagnostics::DocumentDiagnosticReport::Full { items, .. } => {
+                assert_eq!(items, pushed)
+            }
+            report => panic!("expected a full report, got {:?}", report),
+        }
+    }
+
+    #[tokio::test]
+    async fn advertises_the_configured_trigger_characters() {
+        let mut server = TestServer::new();
+        let options = serde_json::json!({ "completion": { "triggerCharacters": ["@", " "] } });
+        let result = server.initialize(options).await;
+        let completion = result.capabilities.completion_provider.unwrap();
+        assert_eq!(
+            completion.trigger_characters,
+            Some(vec![String::from("@"), String::from(" ")])
+        );
+
+        let mut server = TestServer::new();
+        let result = server.initialize(serde_json::Value::Null).await;
+        let completion = result.capabilities.completion_provider.unwrap();
+        assert_eq!(
+            completion.trigger_characters.unwrap().len(),
+            completion::TRIGGER_CHARACTERS.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn overrides_the_severity_of_diagnostics() {
+        let source = "Mob:\n  Skills:\n  - damage{amount=1;amount=2} @self ~onAttack:5\n";
+        let mut server = TestServer::new();
+        let options = serde_json::json!({ "severityOverrides": {
+            "duplicate_mlc_key_error": "off",
+            "trigger_argument_forbidden_error": "hint",
+        } });
+        server.initialize(options).await;
+        let uri = server.open("mobs.yml", source).await;
+        let diagnostics = server.document(&uri).diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+    }
+
+    #[tokio::test]
+    async fn prepares_renames_only_on_metaskill_names() {
+        let server = TestServer::new();
+        let uri = server
+            .open(
+                "skills.yml",
+                "Boom:\n  Skills:\n  - damage{amount=1}  @self\n",
+            )
+            .await;
+        let prepare = |line, character| TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            position: Position::new(line, character),
+        };
+        let response = server
+            .backend()
+            .prepare_rename(prepare(0, 2))
+            .await
+            .unwrap();
+        match response {
+            Some(PrepareRenameResponse::Range(range)) => {
+                assert_eq!(range, Range::new(Position::new(0, 0), Position::new(0, 4)))
+            }
+            response => panic!("expected a range, got {:?}", response),
+        }
+        let response = server
+            .backend()
+            .prepare_rename(prepare(2, 22))
+            .await
+            .unwrap();
+        assert!(response.is_none());
+    }
+
+    #[tokio::test]
+    async fn drops_messages_below_the_log_level() {
+        let mut server = TestServer::new();
+        server
+            .initialize(serde_json::json!({ "logLevel": "error" }))
+            .await;
+        let backend = server.backend();
+        assert!(!backend.should_log(MessageType::INFO));
+        backend.log(MessageType::INFO, String::from("dropped"));
+        backend
+            .log_async(MessageType::INFO, String::from("dropped"))
+            .await;
+        backend
+            .log_async(MessageType::ERROR, String::from("kept"))
+            .await;
+        let messages = server
+            .messages()
+            .iter()
+            .filter(|message| message.method() == "window/logMessage")
+            .filter_map(|message| message.params()?.get("message")?.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["kept"]);
+    }
+
+    #[tokio::test]
+    async fn links_references_to_definitions_in_other_documents() {
+        let server = TestServer::new();
+        server
+            .open("skills.yml", "Boom:\n  Skills:\n  - damage{amount=1}\n")
+            .await;
+        let uri = server
+            .open(
+                "mobs.yml",
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - skill{s=Boom} @self\n",
+            )
+            .await;
+        let links = server
+            .backend()
+            .document_link(DocumentLinkParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0].range,
+            Range::new(Position::new(3, 12), Position::new(3, 16))
+        );
+        assert!(links[0].target.is_none());
+        let link = server
+            .backend()
+            .document_link_resolve(links[0].clone())
+            .await
+            .unwrap();
+        assert_eq!(link.target.unwrap().as_str(), "file:///skills.yml#L1,1");
+    }
+
+    #[tokio::test]
+    async fn saving_reports_unresolved_references() {
+        let mut server = TestServer::new();
+        server
+            .initialize(serde_json::json!({
+                "severityOverrides": { "unresolved_skill_reference_error": "error" },
+            }))
+            .await;
+        let uri = server
+            .open("mobs.yml", "Mob:\n  Type: ZOMBIE\n  Skills: []\n")
+            .await;
+        // read what was sent so far, so the server doesn't wait for the client to catch up
+        server.messages();
+        let text = "Mob:\n  Type: ZOMBIE\n  Skills:\n  - skill{s=Boom}\n";
+        server.change(&uri, 1, text).await;
+        // a plain change doesn't look for unresolved references
+        assert_eq!(server.published_diagnostics(&uri), Some(Vec::new()));
+
+        server
+            .backend()
+            .did_save(DidSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                text: None,
+            })
+            .await;
+        let diagnostics = server.published_diagnostics(&uri).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        // the overrides apply to the diagnostics found on save too
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_logs_and_forgets_documents() {
+        let (service, socket) = LspService::build(Backend::new).finish();
+        // read the logs as they're sent, like a client does
+        tokio::spawn(futures::StreamExt::for_each(socket, |_| async {}));
+        let backend = service.inner();
+        backend.update_document(&TestServer::uri("mobs.yml"), String::from("Mob: {}\n"));
+        for i in 0..3 {
+            backend.log(MessageType::WARNING, format!("warning {}", i));
+        }
+        assert!(!backend.log_tasks.lock().unwrap().is_empty());
+
+        backend.shutdown().await.unwrap();
+        assert!(backend.document_map.is_empty());
+        assert!(backend.log_tasks.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn replaces_diagnostics_on_every_change() {
+        let mut server = TestServer::new();
+        server.initialize(serde_json::Value::Null).await;
+        let text = "Mob:\n  Skills:\n  - damage{amount=1 @self\n";
+        let uri = server.open("mobs.yml", text).await;
+        // read what was sent so far, so the server doesn't wait for the client to catch up
+        server.messages();
+        server.change(&uri, 1, text).await;
+        server.messages();
+        server.change(&uri, 2, text).await;
+        let diagnostics = server.published_diagnostics(&uri).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(server.document(&uri).diagnostics, diagnostics);
+    }
+
+    #[tokio::test]
+    async fn ignores_changes_without_content() {
+        let server = TestServer::new();
+        let uri = server.open("mobs.yml", "Mob:\n  Type: ZOMBIE\n").await;
+        server
+            .backend()
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 1),
+                content_changes: Vec::new(),
+            })
+            .await;
+        assert_eq!(
+            server.document(&uri).source.to_string(),
+            "Mob:\n  Type: ZOMBIE\n"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn serves_semantic_tokens_while_the_document_changes() {
+        let (service, socket) = LspService::build(Backend::new).finish();
+        tokio::spawn(futures::StreamExt::for_each(socket, |_| async {}));
+        // the tasks below need the backend for as long as they run, like the server's requests do
+        let backend: &'static Backend = Box::leak(Box::new(service)).inner();
+        let uri = TestServer::uri("mobs.yml");
+        backend.update_document(&uri, String::from("Mob: {}\n"));
+
+        let changes = tokio::spawn({
+            let uri = uri.clone();
+            async move {
+                for version in 0..100 {
+                    let text = format!("Mob:\n  Skills:\n  - damage{{amount={}}} @self\n", version);
+                    backend
+                        .on_change(TextDocumentItem::new(
+                            uri.clone(),
+                            String::from("yaml"),
+                            version,
+                            text,
+                        ))
+                        .await;
+                }
+            }
+        });
+        let requests = tokio::spawn(async move {
+            for _ in 0..100 {
+                let params = SemanticTokensParams {
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                    text_document: TextDocumentIdentifier::new(uri.clone()),
+                };
+                assert!(backend
+                    .semantic_tokens_full(params)
+                    .await
+                    .unwrap()
+                    .is_some());
+            }
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            changes.await.unwrap();
+            requests.await.unwrap();
+        })
+        .await
+        .expect("the requests and changes should finish");
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    documents::DocumentInfo,
+    mythic_parser::lexer::{MythicScanner, MythicToken, TokenType},
+    utilities::positions_and_ranges::CustomPosition,
+};
+
+/// The brackets that are matched, as pairs of opening and closing brackets.
+/// Angle brackets are left out, as `<` and `>` are also comparisons, e.g. in `<50%`.
+const BRACKETS: &[(TokenType, TokenType)] = &[
+    (TokenType::LeftBrace, TokenType::RightBrace),
+    (TokenType::LeftSquareBracket, TokenType::RightSquareBracket),
+];
+
+/// Finds the bracket matching the one at a position in a document, e.g. the last `}` for the `{`
+/// in `damage{amount=5}`, and returns its start. A bracket right before the position also counts,
+/// so this works with the cursor on either side of it. Brackets in quoted strings are skipped,
+/// as they're part of the string's token.
+pub fn matching_bracket(doc: &DocumentInfo, position: &CustomPosition) -> Option<CustomPosition> {
+    let (range, scalar) = doc
+        .skill_lines()
+        .into_iter()
+        .zip(&doc.skill_scalars)
+        .find(|((range, _), _)| range.start <= *position && *position <= range.end)
+        .map(|((range, _), scalar)| (range, scalar))?;
+    let relative_position = position.relative_from(&range.start);
+    let tokens = MythicScanner::new(scalar.source.clone())
+        .scan_tokens()
+        .into_iter()
+        .filter(|token| token.type_ != TokenType::Eof)
+        .collect::<Vec<_>>();
+    let is_bracket = |token: &MythicToken| {
+        BRACKETS
+            .iter()
+            .any(|(open, close)| token.type_ == *open || token.type_ == *close)
+    };
+    let index = tokens
+        .iter()
+        .position(|token| token.get_range().start == relative_position && is_bracket(token))
+        .or_else(|| {
+            tokens
+                .iter()
+                .position(|token| token.get_range().end == relative_position && is_bracket(token))
+        })?;
+    let matching = matching_token(&tokens, index)?;
+    Some(matching.get_range().start.relative_to(&range.start))
+}
+
+/// Finds the bracket matching the one at `index`, skipping over nested pairs of the same kind.
+fn matching_token(tokens: &[MythicToken], index: usize) -> Option<&MythicToken> {
+    let bracket = tokens[index].type_;
+    let mut depth = 0;
+    if let Some((open, close)) = BRACKETS.iter().find(|(open, _)| *open == bracket) {
+        for token in &tokens[index..] {
+            if token.type_ == *open {
+                depth += 1;
+            } else if token.type_ == *close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(token);
+                }
+            }
+        }
+        return None;
+    }
+    let (open, close) = BRACKETS.iter().find(|(_, close)| *close == bracket)?;
+    for token in tokens[..=index].iter().rev() {
+        if token.type_ == *close {
+            depth += 1;
+        } else if token.type_ == *open {
+            depth -= 1;
+            if depth == 0 {
+                return Some(token);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestServer;
+
+    fn document(source: &str) -> DocumentInfo {
+        TestServer::new()
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string())
+    }
+
+    #[test]
+    fn matches_the_outer_brace() {
+        let doc = document("Mob:\n  Skills:\n  - damage{amount=5} @self\n");
+        assert_eq!(
+            matching_bracket(&doc, &CustomPosition::new(2, 10)),
+            Some(CustomPosition::new(2, 19))
+        );
+        assert_eq!(
+            matching_bracket(&doc, &CustomPosition::new(2, 19)),
+            Some(CustomPosition::new(2, 10))
+        );
+    }
+
+    #[test]
+    fn skips_nested_brackets() {
+        let doc = document("Mob:\n  Skills:\n  - skill{s=[ - damage{a=1} ]} @self\n");
+        // the `{` of `skill` matches the last `}`, not the one of `damage`
+        assert_eq!(
+            matching_bracket(&doc, &CustomPosition::new(2, 9)),
+            Some(CustomPosition::new(2, 29))
+        );
+        assert_eq!(
+            matching_bracket(&doc, &CustomPosition::new(2, 12)),
+            Some(CustomPosition::new(2, 28))
+        );
+    }
+}
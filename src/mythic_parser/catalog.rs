@@ -0,0 +1,56 @@
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+
+use super::{
+    conditions::ConditionInfo, mechanics::Mechanic, placeholders::PlaceholderInfo,
+    targeters::TargeterInfo, triggers::TriggerInfo,
+};
+
+/// Everything known about MythicMobs' mechanics, targeters, triggers, conditions, and placeholders.
+/// Anything that isn't listed is not validated.
+/// Names are looked up ignoring case, e.g. `damage{AMOUNT=5}` is `damage{amount=5}`,
+/// so they're listed in their canonical casing, which is what completion inserts.
+#[derive(Debug, Deserialize)]
+pub struct Catalog {
+    pub mechanics: Vec<Mechanic>,
+    pub targeters: Vec<TargeterInfo>,
+    pub triggers: Vec<TriggerInfo>,
+    pub conditions: Vec<ConditionInfo>,
+    pub placeholders: Vec<PlaceholderInfo>,
+}
+
+static CATALOG: LazyLock<Catalog> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("catalog.json")).expect("the catalog should be valid")
+});
+
+/// Returns the catalog embedded in the server, loading it on first use.
+pub fn catalog() -> &'static Catalog {
+    &CATALOG
+}
+
+/// Whether any of the given names matches `name`, ignoring case like MythicMobs does.
+pub(super) fn has_name(names: &[String], name: &str) -> bool {
+    names.iter().any(|other| other.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_embedded_catalog() {
+        let damage = catalog()
+            .mechanics
+            .iter()
+            .find(|mechanic| mechanic.names[0] == "damage")
+            .expect("the catalog should list damage");
+        assert!(damage
+            .attributes
+            .iter()
+            .any(|attribute| attribute.names[0] == "amount"));
+        assert!(!catalog().targeters.is_empty());
+        assert!(!catalog().triggers.is_empty());
+        assert!(!catalog().conditions.is_empty());
+    }
+}
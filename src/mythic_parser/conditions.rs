@@ -0,0 +1,10 @@
+use serde::Deserialize;
+
+/// A known MythicMobs condition, e.g. `health` in `?health{h=<50%}`,
+/// as listed in the [`catalog`](super::catalog::catalog).
+#[derive(Debug, Deserialize)]
+pub struct ConditionInfo {
+    /// The condition's name followed by its aliases.
+    pub names: Vec<String>,
+    pub description: Option<String>,
+}
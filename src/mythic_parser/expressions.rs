@@ -1,11 +1,19 @@
-use tower_lsp::lsp_types::{Position, Range};
+use crate::utilities::positions_and_ranges::{CustomPosition, CustomRange};
 
-use crate::utilities::positions_and_ranges::CustomRange;
-
-use super::lexer::MythicToken;
+use super::{
+    lexer::{MythicToken, TokenType},
+    mechanics::{mechanic, AttributeType},
+};
 
 pub trait ExprTrait {
     fn get_range(&self) -> CustomRange;
+    /// Returns a short, human-readable description of this expression, e.g. `targeter: @self`.
+    fn label(&self) -> String;
+}
+
+/// Returns the source text of a token.
+fn lexeme(token: &MythicToken) -> &str {
+    token.lexeme.as_deref().unwrap_or_default()
 }
 
 #[derive(Debug)]
@@ -35,6 +43,176 @@ impl SkillLine {
             health_modifier,
         }
     }
+    pub fn mechanic(&self) -> &GenericNameAndMlc {
+        &self.mechanic
+    }
+    pub fn targeter(&self) -> Option<&Targeter> {
+        self.targeter.as_deref()
+    }
+    pub fn trigger(&self) -> Option<&Trigger> {
+        self.trigger.as_deref()
+    }
+    pub fn conditions(&self) -> &[InlineCondition] {
+        &self.conditions
+    }
+    pub fn chance(&self) -> Option<&Chance> {
+        self.chance.as_deref()
+    }
+    /// Returns the range of this skill line, from the mechanic to its last modifier.
+    pub fn get_range(&self) -> Option<CustomRange> {
+        let start = self.mechanic.get_range()?.start;
+        let ends = [
+            self.mechanic.get_range().map(|range| range.end),
+            self.targeter
+                .as_ref()
+                .map(|targeter| targeter.get_range().end),
+            self.trigger.as_ref().map(|trigger| trigger.get_range().end),
+            self.chance.as_ref().map(|chance| chance.get_range().end),
+            self.health_modifier
+                .as_ref()
+                .map(|modifier| modifier.get_range().end),
+        ];
+        let end = ends
+            .into_iter()
+            .flatten()
+            .chain(
+                self.conditions
+                    .iter()
+                    .map(|condition| condition.get_range().end),
+            )
+            .max()?;
+        Some(CustomRange::new(start, end))
+    }
+    /// Renders this skill line back to canonical Mythic syntax, e.g.
+    /// `damage{amount=5} @Target ~onAttack ?!raining 0.5 <50%`.
+    /// Parts are separated by single spaces in the order above, and MLCs by `;` with no spaces.
+    /// MLC values are kept as written, including any whitespace inside them.
+    pub fn to_source(&self) -> String {
+        let mut parts = vec![self.mechanic.to_source()];
+        parts.extend(self.targeter.as_ref().map(|targeter| targeter.to_source()));
+        parts.extend(self.trigger.as_ref().map(|trigger| trigger.to_source()));
+        parts.extend(
+            self.conditions
+                .iter()
+                .map(|condition| condition.to_source()),
+        );
+        parts.extend(self.chance.as_ref().map(|chance| chance.to_source()));
+        parts.extend(
+            self.health_modifier
+                .as_ref()
+                .map(|modifier| modifier.to_source()),
+        );
+        parts.join(" ")
+    }
+    /// Returns the most specific expression enclosing the given position,
+    /// or `None` if the position is outside of this skill line.
+    /// The position is relative to the start of the skill line.
+    pub fn node_at(&self, position: &CustomPosition) -> Option<NodeRef<'_>> {
+        NodeRef::SkillLine(self).node_at(position)
+    }
+    /// Like [`Self::node_at`], but returns every expression enclosing the position,
+    /// starting with this skill line.
+    pub fn path_at(&self, position: &CustomPosition) -> Vec<NodeRef<'_>> {
+        NodeRef::SkillLine(self).path_at(position)
+    }
+    /// Returns every placeholder in this skill line, in source order.
+    /// This includes placeholders nested in other placeholders and in inline skills.
+    pub fn placeholders(&self) -> Vec<&Placeholder> {
+        fn collect<'a>(node: NodeRef<'a>, placeholders: &mut Vec<&'a Placeholder>) {
+            if let NodeRef::Placeholder(placeholder) = node {
+                placeholders.push(placeholder);
+            }
+            for child in node.children() {
+                collect(child, placeholders);
+            }
+        }
+        let mut placeholders = Vec::new();
+        collect(NodeRef::SkillLine(self), &mut placeholders);
+        placeholders
+    }
+    /// Returns every MLC in this skill line, including the ones in its targeter, conditions,
+    /// placeholders, and inline skills.
+    pub fn mlcs(&self) -> Vec<&Mlc> {
+        fn collect<'a>(node: NodeRef<'a>, mlcs: &mut Vec<&'a Mlc>) {
+            if let NodeRef::Mlc(mlc) = node {
+                mlcs.push(mlc);
+            }
+            for child in node.children() {
+                collect(child, mlcs);
+            }
+        }
+        let mut mlcs = Vec::new();
+        collect(NodeRef::SkillLine(self), &mut mlcs);
+        mlcs
+    }
+    /// Returns the skill lines of every inline skill directly inside this skill line's mechanic.
+    pub fn inline_skill_lines(&self) -> Vec<&SkillLine> {
+        self.mechanic
+            .mlc()
+            .map(|mlc| {
+                mlc.mlcs()
+                    .iter()
+                    .filter_map(|mlc| match mlc.value() {
+                        MlcValueContainer::InlineSkill(inline_skill) => Some(inline_skill),
+                        MlcValueContainer::MlcValue(_) => None,
+                    })
+                    .flat_map(|inline_skill| {
+                        inline_skill
+                            .skills()
+                            .iter()
+                            .map(|container| container.skill())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    /// Returns the name and range of every metaskill this skill line calls,
+    /// e.g. `Other` in `skill{s=Other}`.
+    /// Skills nested in inline skills are included, but [`Self::bare_inline_skills`] aren't.
+    pub fn skill_references(&self) -> Vec<(String, CustomRange)> {
+        self.skill_values()
+            .into_iter()
+            .filter(|value| !value.has_mlc())
+            .filter_map(|value| Some((value.value(), value.get_range()?)))
+            .chain(
+                self.inline_skill_lines()
+                    .into_iter()
+                    .flat_map(|skill_line| skill_line.skill_references()),
+            )
+            .collect()
+    }
+    /// Returns every inline skill directly in this skill line's mechanic that isn't wrapped in
+    /// a list, e.g. `damage{a=1}` in `skill{s=damage{a=1}}`, which MythicMobs reads as a
+    /// metaskill name instead.
+    pub fn bare_inline_skills(&self) -> Vec<&MlcValue> {
+        self.skill_values()
+            .into_iter()
+            .filter(|value| value.has_mlc())
+            .collect()
+    }
+    /// Returns the values that name the metaskill to call, e.g. `Other` in `skill{s=Other}`.
+    fn skill_values(&self) -> Vec<&MlcValue> {
+        let Some(mechanic) = mechanic(&self.mechanic.name().value()) else {
+            return Vec::new();
+        };
+        self.mechanic
+            .mlc()
+            .map(|mlc| {
+                mlc.mlcs()
+                    .iter()
+                    .filter(|mlc| {
+                        mechanic
+                            .attribute(&mlc.key_name())
+                            .is_some_and(|attribute| attribute.type_ == AttributeType::Metaskill)
+                    })
+                    .filter_map(|mlc| match mlc.value() {
+                        MlcValueContainer::MlcValue(value) => Some(value),
+                        MlcValueContainer::InlineSkill(_) => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug)]
@@ -46,6 +224,20 @@ impl GenericString {
     pub fn new(tokens: Vec<MythicToken>) -> Self {
         Self { tokens }
     }
+    /// Returns the source text of this string.
+    pub fn value(&self) -> String {
+        self.tokens
+            .iter()
+            .filter_map(|token| token.lexeme.clone())
+            .collect()
+    }
+    /// Returns the range of this string, or `None` if it is empty.
+    pub fn get_range(&self) -> Option<CustomRange> {
+        Some(CustomRange::new(
+            self.tokens.first()?.get_range().start,
+            self.tokens.last()?.get_range().end,
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -60,28 +252,85 @@ impl GenericNameAndMlc {
     pub fn new(name: GenericString, mlc: Option<Box<MlcContainer>>) -> Self {
         Self { name, mlc }
     }
+    pub fn name(&self) -> &GenericString {
+        &self.name
+    }
+    pub fn mlc(&self) -> Option<&MlcContainer> {
+        self.mlc.as_deref()
+    }
+    pub fn to_source(&self) -> String {
+        let mlc = self.mlc.as_ref().map(|mlc| mlc.to_source());
+        format!("{}{}", self.name.value(), mlc.unwrap_or_default())
+    }
+    /// Returns the range of the name and MLC, or `None` if both are empty.
+    pub fn get_range(&self) -> Option<CustomRange> {
+        let mlc = self.mlc.as_ref().map(|mlc| mlc.get_range());
+        let start = self.name.get_range().or(mlc)?.start;
+        let end = mlc.or(self.name.get_range())?.end;
+        Some(CustomRange::new(start, end))
+    }
+}
+
+/// The name of a targeter, e.g. `self` in `@self`.
+#[derive(Debug)]
+pub enum TargeterName {
+    Identifier(MythicToken),
+    /// A targeter chosen at runtime, e.g. `@<skill.mytarget>`.
+    Placeholder(Placeholder),
+}
+
+impl TargeterName {
+    pub fn get_range(&self) -> CustomRange {
+        match self {
+            TargeterName::Identifier(name) => name.get_range(),
+            TargeterName::Placeholder(placeholder) => placeholder.get_range(),
+        }
+    }
+    pub fn to_source(&self) -> String {
+        match self {
+            TargeterName::Identifier(name) => lexeme(name).to_string(),
+            TargeterName::Placeholder(placeholder) => placeholder.to_source(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Targeter {
     at: MythicToken,
-    name: MythicToken,
+    name: TargeterName,
     mlc: Option<Box<MlcContainer>>,
 }
 
 impl ExprTrait for Targeter {
     fn get_range(&self) -> CustomRange {
-        CustomRange::new(
-            self.at.get_range().start,
-            self.mlc.as_ref().unwrap().get_range().end,
-        )
+        let end = match &self.mlc {
+            Some(mlc) => mlc.get_range().end,
+            None => self.name.get_range().end,
+        };
+        CustomRange::new(self.at.get_range().start, end)
+    }
+    fn label(&self) -> String {
+        format!("targeter: @{}", self.name.to_source())
     }
 }
 
 impl Targeter {
-    pub fn new(at: MythicToken, name: MythicToken, mlc: Option<Box<MlcContainer>>) -> Self {
+    pub fn new(at: MythicToken, name: TargeterName, mlc: Option<Box<MlcContainer>>) -> Self {
         Self { at, name, mlc }
     }
+    pub fn at(&self) -> &MythicToken {
+        &self.at
+    }
+    pub fn name(&self) -> &TargeterName {
+        &self.name
+    }
+    pub fn mlc(&self) -> Option<&MlcContainer> {
+        self.mlc.as_deref()
+    }
+    pub fn to_source(&self) -> String {
+        let mlc = self.mlc.as_ref().map(|mlc| mlc.to_source());
+        format!("@{}{}", self.name.to_source(), mlc.unwrap_or_default())
+    }
 }
 
 #[derive(Debug)]
@@ -106,6 +355,37 @@ impl Trigger {
             arg,
         }
     }
+    pub fn caret(&self) -> &MythicToken {
+        &self.caret
+    }
+    pub fn name(&self) -> &GenericString {
+        &self.name
+    }
+    pub fn arg(&self) -> Option<&GenericString> {
+        self.arg.as_deref()
+    }
+    pub fn to_source(&self) -> String {
+        match &self.arg {
+            Some(arg) => format!("~{}:{}", self.name.value(), arg.value()),
+            None => format!("~{}", self.name.value()),
+        }
+    }
+}
+
+impl ExprTrait for Trigger {
+    fn get_range(&self) -> CustomRange {
+        let end = self
+            .arg
+            .as_ref()
+            .and_then(|arg| arg.get_range())
+            .or_else(|| self.colon.as_ref().map(|colon| colon.get_range()))
+            .or_else(|| self.name.get_range())
+            .unwrap_or_else(|| self.caret.get_range());
+        CustomRange::new(self.caret.get_range().start, end.end)
+    }
+    fn label(&self) -> String {
+        format!("trigger: {}", self.to_source())
+    }
 }
 
 #[derive(Debug)]
@@ -133,16 +413,106 @@ impl InlineCondition {
             mlc,
         }
     }
+    pub fn question_mark(&self) -> &MythicToken {
+        &self.question_mark
+    }
+    pub fn name(&self) -> &MythicToken {
+        &self.name
+    }
+    pub fn mlc(&self) -> Option<&MlcContainer> {
+        self.mlc.as_deref()
+    }
+    pub fn to_source(&self) -> String {
+        let mlc = self.mlc.as_ref().map(|mlc| mlc.to_source());
+        format!(
+            "?{}{}{}{}",
+            if self.exclamation_mark.is_some() {
+                "!"
+            } else {
+                ""
+            },
+            if self.tilde.is_some() { "~" } else { "" },
+            lexeme(&self.name),
+            mlc.unwrap_or_default()
+        )
+    }
+}
+
+impl ExprTrait for InlineCondition {
+    fn get_range(&self) -> CustomRange {
+        let end = match &self.mlc {
+            Some(mlc) => mlc.get_range().end,
+            None => self.name.get_range().end,
+        };
+        CustomRange::new(self.question_mark.get_range().start, end)
+    }
+    fn label(&self) -> String {
+        format!("condition: {}", self.to_source())
+    }
 }
 
 #[derive(Debug)]
 pub struct Chance {
-    token: MythicToken,
+    /// The `-` of a negative chance, which is never valid but still parsed to report it.
+    minus: Option<MythicToken>,
+    value: MythicToken,
+    percent: Option<MythicToken>,
+    /// The chance normalized to a fraction, so `0.25` and `25%` are both `0.25`.
+    fraction: Option<f64>,
 }
 
 impl Chance {
-    pub fn new(token: MythicToken) -> Self {
-        Self { token }
+    pub fn new(
+        minus: Option<MythicToken>,
+        value: MythicToken,
+        percent: Option<MythicToken>,
+    ) -> Self {
+        let sign = if minus.is_some() { -1.0 } else { 1.0 };
+        let scale = if percent.is_some() { 100.0 } else { 1.0 };
+        let fraction = value
+            .lexeme
+            .as_ref()
+            .and_then(|lexeme| lexeme.parse::<f64>().ok())
+            .map(|number| sign * number / scale);
+        Self {
+            minus,
+            value,
+            percent,
+            fraction,
+        }
+    }
+    /// Returns the chance as a fraction, e.g. `0.5` for both `0.5` and `50%`.
+    /// This is only between 0 and 1 for valid chances, see [`Self::is_possible`].
+    pub fn fraction(&self) -> Option<f64> {
+        self.fraction
+    }
+    /// Whether the chance is between 0% and 100%. Chances that can't be read as a number are
+    /// assumed to be possible, as they're reported by the parser instead.
+    pub fn is_possible(&self) -> bool {
+        self.fraction
+            .is_none_or(|fraction| (0.0..=1.0).contains(&fraction))
+    }
+    pub fn to_source(&self) -> String {
+        format!(
+            "{}{}{}",
+            if self.minus.is_some() { "-" } else { "" },
+            lexeme(&self.value),
+            if self.percent.is_some() { "%" } else { "" },
+        )
+    }
+}
+
+impl ExprTrait for Chance {
+    fn get_range(&self) -> CustomRange {
+        let end = match &self.percent {
+            Some(percent) => percent.get_range().end,
+            None => self.value.get_range().end,
+        };
+        let start = self.minus.as_ref().unwrap_or(&self.value);
+        CustomRange::new(start.get_range().start, end)
+    }
+    fn label(&self) -> String {
+        format!("chance: {}", self.to_source())
     }
 }
 
@@ -156,6 +526,39 @@ impl HealthModifier {
     pub fn new(operator: MythicToken, value: HealthModifierValueOrRange) -> Self {
         Self { operator, value }
     }
+    pub fn operator(&self) -> &MythicToken {
+        &self.operator
+    }
+    pub fn value(&self) -> &HealthModifierValueOrRange {
+        &self.value
+    }
+    pub fn to_source(&self) -> String {
+        let value = match &self.value {
+            HealthModifierValueOrRange::Value(value) => value.to_source(),
+            HealthModifierValueOrRange::Range(min, max) => {
+                format!("{}-{}", min.to_source(), max.to_source())
+            }
+        };
+        format!("{}{}", lexeme(&self.operator), value)
+    }
+}
+
+impl ExprTrait for HealthModifier {
+    fn get_range(&self) -> CustomRange {
+        let last = match &self.value {
+            HealthModifierValueOrRange::Value(value)
+            | HealthModifierValueOrRange::Range(_, value) => value,
+        };
+        let end = match last {
+            HealthModifierValue::Absolute(token) | HealthModifierValue::Percentage(_, token) => {
+                token.get_range().end
+            }
+        };
+        CustomRange::new(self.operator.get_range().start, end)
+    }
+    fn label(&self) -> String {
+        format!("health modifier: {}", self.to_source())
+    }
 }
 
 #[derive(Debug)]
@@ -171,6 +574,15 @@ pub enum HealthModifierValue {
     Percentage(MythicToken, MythicToken),
 }
 
+impl HealthModifierValue {
+    pub fn to_source(&self) -> String {
+        match self {
+            HealthModifierValue::Absolute(value) => lexeme(value).to_string(),
+            HealthModifierValue::Percentage(value, _) => format!("{}%", lexeme(value)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MlcContainer {
     left_brace: MythicToken,
@@ -185,6 +597,14 @@ impl ExprTrait for MlcContainer {
             self.right_brace.get_range().end,
         )
     }
+    fn label(&self) -> String {
+        let keys = self
+            .mlcs
+            .iter()
+            .map(|mlc| mlc.key_name())
+            .collect::<Vec<_>>();
+        format!("mlcs: {}", keys.join(", "))
+    }
 }
 
 impl MlcContainer {
@@ -195,6 +615,17 @@ impl MlcContainer {
             right_brace,
         }
     }
+    pub fn mlcs(&self) -> &[Mlc] {
+        &self.mlcs
+    }
+    pub fn to_source(&self) -> String {
+        let mlcs = self
+            .mlcs
+            .iter()
+            .map(|mlc| mlc.to_source())
+            .collect::<Vec<_>>();
+        format!("{{{}}}", mlcs.join(";"))
+    }
 }
 
 #[derive(Debug)]
@@ -202,22 +633,47 @@ pub struct Mlc {
     key: MythicToken,
     equals: MythicToken,
     value: MlcValueContainer,
-    semicolon: Option<MythicToken>,
 }
 
 impl Mlc {
-    pub fn new(
-        key: MythicToken,
-        equals: MythicToken,
-        value: MlcValueContainer,
-        semicolon: Option<MythicToken>,
-    ) -> Self {
-        Self {
-            key,
-            equals,
-            value,
-            semicolon,
-        }
+    pub fn new(key: MythicToken, equals: MythicToken, value: MlcValueContainer) -> Self {
+        Self { key, equals, value }
+    }
+    pub fn key(&self) -> &MythicToken {
+        &self.key
+    }
+    pub fn equals(&self) -> &MythicToken {
+        &self.equals
+    }
+    /// Returns the key's name, e.g. `amount` in `amount=5`.
+    pub fn key_name(&self) -> String {
+        self.key.lexeme.clone().unwrap_or_default()
+    }
+    pub fn value(&self) -> &MlcValueContainer {
+        &self.value
+    }
+    pub fn to_source(&self) -> String {
+        let value = match &self.value {
+            MlcValueContainer::MlcValue(value) => value.to_source(),
+            MlcValueContainer::InlineSkill(inline_skill) => inline_skill.to_source(),
+        };
+        format!("{}={}", self.key_name(), value)
+    }
+}
+
+impl ExprTrait for Mlc {
+    fn get_range(&self) -> CustomRange {
+        let end = match &self.value {
+            MlcValueContainer::MlcValue(value) => value.get_range(),
+            MlcValueContainer::InlineSkill(inline_skill) => Some(inline_skill.get_range()),
+        };
+        CustomRange::new(
+            self.key.get_range().start,
+            end.unwrap_or_else(|| self.equals.get_range()).end,
+        )
+    }
+    fn label(&self) -> String {
+        format!("mlc: {}", self.key_name())
     }
 }
 
@@ -236,61 +692,499 @@ impl MlcValue {
     pub fn new(identifiers: Vec<MlcValueIdentifier>) -> Self {
         Self { identifiers }
     }
+    pub fn identifiers(&self) -> &[MlcValueIdentifier] {
+        &self.identifiers
+    }
+    /// Returns the source text of this value, including any placeholders.
+    pub fn value(&self) -> String {
+        self.identifiers
+            .iter()
+            .map(|identifier| match identifier {
+                MlcValueIdentifier::Identifiers(tokens) => tokens
+                    .iter()
+                    .filter_map(|token| token.lexeme.clone())
+                    .collect(),
+                MlcValueIdentifier::Placeholder(placeholder) => placeholder
+                    .get_range()
+                    .get_from(&placeholder.left_angle_bracket.source),
+                MlcValueIdentifier::FormatTag(tag) => tag.to_source(),
+            })
+            .collect()
+    }
+    /// Like [`Self::value`], but with placeholders in canonical syntax.
+    pub fn to_source(&self) -> String {
+        self.identifiers
+            .iter()
+            .map(|identifier| match identifier {
+                MlcValueIdentifier::Identifiers(tokens) => tokens.iter().map(lexeme).collect(),
+                MlcValueIdentifier::Placeholder(placeholder) => placeholder.to_source(),
+                MlcValueIdentifier::FormatTag(tag) => tag.to_source(),
+            })
+            .collect()
+    }
+    /// Returns the placeholders directly inside this value.
+    /// Placeholders nested in their MLCs, like `<caster.level>` in `<skill.x{a=<caster.level>}>`,
+    /// are reached through [`Placeholder::parts`].
+    pub fn placeholders(&self) -> impl Iterator<Item = &Placeholder> {
+        self.identifiers
+            .iter()
+            .filter_map(|identifier| match identifier {
+                MlcValueIdentifier::Placeholder(placeholder) => Some(placeholder),
+                MlcValueIdentifier::Identifiers(_) | MlcValueIdentifier::FormatTag(_) => None,
+            })
+    }
+    /// Whether this value contains an MLC, e.g. `damage{a=1}`, rather than just text.
+    pub fn has_mlc(&self) -> bool {
+        self.identifiers.iter().any(|identifier| match identifier {
+            MlcValueIdentifier::Identifiers(tokens) => tokens
+                .iter()
+                .any(|token| token.type_ == TokenType::LeftBrace),
+            MlcValueIdentifier::Placeholder(_) | MlcValueIdentifier::FormatTag(_) => false,
+        })
+    }
+    /// Whether this value contains a placeholder, i.e. it's only known at runtime.
+    pub fn has_placeholders(&self) -> bool {
+        self.identifiers
+            .iter()
+            .any(|identifier| matches!(identifier, MlcValueIdentifier::Placeholder(_)))
+    }
+    /// Returns this value as a number, or `None` if it isn't one.
+    /// Signs are scanned as separate tokens, but are part of the value, so `-1.5` and `+2` work.
+    pub fn as_number(&self) -> Option<f64> {
+        self.value().trim().parse().ok()
+    }
+    /// Returns this value as a range of numbers like `1to5`, or `None` if it isn't one.
+    pub fn as_number_range(&self) -> Option<(f64, f64)> {
+        let value = self.value();
+        let (min, max) = value.trim().split_once("to")?;
+        Some((min.parse().ok()?, max.parse().ok()?))
+    }
+    /// Returns this value as a boolean, or `None` if it isn't `true` or `false`.
+    pub fn as_bool(&self) -> Option<bool> {
+        let mut tokens = match self.identifiers.as_slice() {
+            [MlcValueIdentifier::Identifiers(tokens)] => tokens
+                .iter()
+                .filter(|token| token.type_ != TokenType::Space),
+            _ => return None,
+        };
+        match (tokens.next(), tokens.next()) {
+            (Some(token), None) => token.as_bool(),
+            _ => None,
+        }
+    }
+    /// Returns the range of this value, or `None` if it is empty.
+    pub fn get_range(&self) -> Option<CustomRange> {
+        let ranges = self
+            .identifiers
+            .iter()
+            .filter_map(|identifier| match identifier {
+                MlcValueIdentifier::Identifiers(tokens) => Some(CustomRange::new(
+                    tokens.first()?.get_range().start,
+                    tokens.last()?.get_range().end,
+                )),
+                MlcValueIdentifier::Placeholder(placeholder) => Some(placeholder.get_range()),
+                MlcValueIdentifier::FormatTag(tag) => Some(tag.get_range()),
+            })
+            .collect::<Vec<_>>();
+        Some(CustomRange::new(ranges.first()?.start, ranges.last()?.end))
+    }
 }
 
 #[derive(Debug)]
 pub enum MlcValueIdentifier {
     Identifiers(Vec<MythicToken>),
     Placeholder(Placeholder),
+    FormatTag(FormatTag),
+}
+
+/// A MiniMessage formatting tag in a value, e.g. `<#ff0000>` or `<gradient:red:blue>`.
+#[derive(Debug)]
+pub struct FormatTag {
+    left_angle_bracket: MythicToken,
+    contents: Vec<MythicToken>,
+    /// `None` if the tag is unterminated.
+    right_angle_bracket: Option<MythicToken>,
+}
+
+impl FormatTag {
+    pub fn new(
+        left_angle_bracket: MythicToken,
+        contents: Vec<MythicToken>,
+        right_angle_bracket: Option<MythicToken>,
+    ) -> Self {
+        Self {
+            left_angle_bracket,
+            contents,
+            right_angle_bracket,
+        }
+    }
+    /// Returns the text between the angle brackets, e.g. `#ff0000` for `<#ff0000>`.
+    pub fn contents(&self) -> String {
+        self.contents.iter().map(lexeme).collect()
+    }
+    /// Renders this tag, adding the closing `>` if it was missing.
+    pub fn to_source(&self) -> String {
+        format!("<{}>", self.contents())
+    }
+}
+
+impl ExprTrait for FormatTag {
+    fn get_range(&self) -> CustomRange {
+        let end = self
+            .right_angle_bracket
+            .as_ref()
+            .or(self.contents.last())
+            .unwrap_or(&self.left_angle_bracket);
+        CustomRange::new(
+            self.left_angle_bracket.get_range().start,
+            end.get_range().end,
+        )
+    }
+    fn label(&self) -> String {
+        format!("format tag: {}", self.to_source())
+    }
 }
 
 #[derive(Debug)]
 pub struct Placeholder {
     left_angle_bracket: MythicToken,
     identifiers: Vec<GenericNameAndMlc>,
-    dots: Vec<MythicToken>,
-    right_angle_bracket: MythicToken,
+    /// `None` if the placeholder is unterminated.
+    right_angle_bracket: Option<MythicToken>,
 }
 
 impl Placeholder {
     pub fn new(
         left_angle_bracket: MythicToken,
         identifiers: Vec<GenericNameAndMlc>,
-        dots: Vec<MythicToken>,
-        right_angle_bracket: MythicToken,
+        right_angle_bracket: Option<MythicToken>,
     ) -> Self {
         Self {
             left_angle_bracket,
             identifiers,
-            dots,
             right_angle_bracket,
         }
     }
+    /// Returns the dot-separated parts, e.g. `skill` and `x{a=1}` for `<skill.x{a=1}>`.
+    pub fn parts(&self) -> &[GenericNameAndMlc] {
+        &self.identifiers
+    }
+    /// Renders this placeholder, adding the closing `>` if it was missing.
+    pub fn to_source(&self) -> String {
+        let parts = self
+            .identifiers
+            .iter()
+            .map(|part| part.to_source())
+            .collect::<Vec<_>>();
+        format!("<{}>", parts.join("."))
+    }
+}
+
+impl ExprTrait for Placeholder {
+    fn get_range(&self) -> CustomRange {
+        let end = match &self.right_angle_bracket {
+            Some(right_angle_bracket) => Some(right_angle_bracket.get_range()),
+            None => self.identifiers.last().and_then(|part| part.get_range()),
+        };
+        CustomRange::new(
+            self.left_angle_bracket.get_range().start,
+            end.unwrap_or_else(|| self.left_angle_bracket.get_range())
+                .end,
+        )
+    }
+    fn label(&self) -> String {
+        format!("placeholder: {}", self.to_source())
+    }
 }
 
 #[derive(Debug)]
 pub struct InlineSkill {
     left_square_bracket: MythicToken,
     skills: Vec<InlineSkillSkillContainer>,
+    /// `None` if the inline skill is unterminated.
+    right_square_bracket: Option<MythicToken>,
 }
 
 impl InlineSkill {
-    pub fn new(left_square_bracket: MythicToken, skills: Vec<InlineSkillSkillContainer>) -> Self {
+    pub fn new(
+        left_square_bracket: MythicToken,
+        skills: Vec<InlineSkillSkillContainer>,
+        right_square_bracket: Option<MythicToken>,
+    ) -> Self {
         Self {
             left_square_bracket,
             skills,
+            right_square_bracket,
+        }
+    }
+    pub fn skills(&self) -> &[InlineSkillSkillContainer] {
+        &self.skills
+    }
+    /// Renders this inline skill as `[ - first - second ]`.
+    pub fn to_source(&self) -> String {
+        let skills = self
+            .skills
+            .iter()
+            .map(|container| format!("- {} ", container.skill.to_source()))
+            .collect::<String>();
+        format!("[ {}]", skills)
+    }
+}
+
+impl ExprTrait for InlineSkill {
+    fn get_range(&self) -> CustomRange {
+        let end = match &self.right_square_bracket {
+            Some(right_square_bracket) => right_square_bracket.get_range(),
+            None => self
+                .skills
+                .last()
+                .and_then(|container| container.skill.get_range())
+                .unwrap_or_else(|| self.left_square_bracket.get_range()),
+        };
+        CustomRange::new(self.left_square_bracket.get_range().start, end.end)
+    }
+    fn label(&self) -> String {
+        match self.skills.len() {
+            1 => String::from("inline skill: 1 skill"),
+            count => format!("inline skill: {} skills", count),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct InlineSkillSkillContainer {
-    dash: MythicToken,
     skill: SkillLine,
 }
 
 impl InlineSkillSkillContainer {
-    pub fn new(dash: MythicToken, skill: SkillLine) -> Self {
-        Self { dash, skill }
+    pub fn new(skill: SkillLine) -> Self {
+        Self { skill }
+    }
+    pub fn skill(&self) -> &SkillLine {
+        &self.skill
+    }
+}
+
+/// A reference to any expression in a skill line, e.g. the node under the cursor.
+/// See [`SkillLine::node_at`].
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    SkillLine(&'a SkillLine),
+    Mechanic(&'a GenericNameAndMlc),
+    Targeter(&'a Targeter),
+    Trigger(&'a Trigger),
+    InlineCondition(&'a InlineCondition),
+    Chance(&'a Chance),
+    HealthModifier(&'a HealthModifier),
+    MlcContainer(&'a MlcContainer),
+    Mlc(&'a Mlc),
+    MlcValue(&'a MlcValue),
+    Placeholder(&'a Placeholder),
+    InlineSkill(&'a InlineSkill),
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn get_range(&self) -> Option<CustomRange> {
+        match self {
+            NodeRef::SkillLine(expr) => expr.get_range(),
+            NodeRef::Mechanic(expr) => expr.get_range(),
+            NodeRef::Targeter(expr) => Some(expr.get_range()),
+            NodeRef::Trigger(expr) => Some(expr.get_range()),
+            NodeRef::InlineCondition(expr) => Some(expr.get_range()),
+            NodeRef::Chance(expr) => Some(expr.get_range()),
+            NodeRef::HealthModifier(expr) => Some(expr.get_range()),
+            NodeRef::MlcContainer(expr) => Some(expr.get_range()),
+            NodeRef::Mlc(expr) => Some(expr.get_range()),
+            NodeRef::MlcValue(expr) => expr.get_range(),
+            NodeRef::Placeholder(expr) => Some(expr.get_range()),
+            NodeRef::InlineSkill(expr) => Some(expr.get_range()),
+        }
+    }
+    /// Returns a short, human-readable description of this expression, e.g. `mechanic: damage`.
+    /// See [`ExprTrait::label`].
+    pub fn label(&self) -> String {
+        match self {
+            NodeRef::SkillLine(expr) => format!("skill line: {}", expr.mechanic.name().value()),
+            NodeRef::Mechanic(expr) => format!("mechanic: {}", expr.name().value()),
+            NodeRef::Targeter(expr) => expr.label(),
+            NodeRef::Trigger(expr) => expr.label(),
+            NodeRef::InlineCondition(expr) => expr.label(),
+            NodeRef::Chance(expr) => expr.label(),
+            NodeRef::HealthModifier(expr) => expr.label(),
+            NodeRef::MlcContainer(expr) => expr.label(),
+            NodeRef::Mlc(expr) => expr.label(),
+            NodeRef::MlcValue(expr) => format!("value: {}", expr.to_source()),
+            NodeRef::Placeholder(expr) => expr.label(),
+            NodeRef::InlineSkill(expr) => expr.label(),
+        }
+    }
+    /// Returns the expressions directly inside this one.
+    pub fn children(&self) -> Vec<NodeRef<'a>> {
+        match *self {
+            NodeRef::SkillLine(expr) => {
+                let mut children = vec![NodeRef::Mechanic(&expr.mechanic)];
+                children.extend(expr.targeter.as_deref().map(NodeRef::Targeter));
+                children.extend(expr.trigger.as_deref().map(NodeRef::Trigger));
+                children.extend(expr.conditions.iter().map(NodeRef::InlineCondition));
+                children.extend(expr.chance.as_deref().map(NodeRef::Chance));
+                children.extend(expr.health_modifier.as_deref().map(NodeRef::HealthModifier));
+                children
+            }
+            NodeRef::Mechanic(expr) => expr.mlc().map(NodeRef::MlcContainer).into_iter().collect(),
+            NodeRef::Targeter(expr) => {
+                let placeholder = match &expr.name {
+                    TargeterName::Placeholder(placeholder) => {
+                        Some(NodeRef::Placeholder(placeholder))
+                    }
+                    TargeterName::Identifier(_) => None,
+                };
+                placeholder
+                    .into_iter()
+                    .chain(expr.mlc().map(NodeRef::MlcContainer))
+                    .collect()
+            }
+            NodeRef::InlineCondition(expr) => {
+                expr.mlc().map(NodeRef::MlcContainer).into_iter().collect()
+            }
+            NodeRef::MlcContainer(expr) => expr.mlcs.iter().map(NodeRef::Mlc).collect(),
+            NodeRef::Mlc(expr) => match &expr.value {
+                MlcValueContainer::MlcValue(value) => vec![NodeRef::MlcValue(value)],
+                MlcValueContainer::InlineSkill(inline_skill) => {
+                    vec![NodeRef::InlineSkill(inline_skill)]
+                }
+            },
+            NodeRef::MlcValue(expr) => expr.placeholders().map(NodeRef::Placeholder).collect(),
+            NodeRef::Placeholder(expr) => expr
+                .parts()
+                .iter()
+                .filter_map(|part| part.mlc().map(NodeRef::MlcContainer))
+                .collect(),
+            NodeRef::InlineSkill(expr) => expr
+                .skills
+                .iter()
+                .map(|container| NodeRef::SkillLine(&container.skill))
+                .collect(),
+            NodeRef::Trigger(_) | NodeRef::Chance(_) | NodeRef::HealthModifier(_) => Vec::new(),
+        }
+    }
+    /// Returns the opening and closing delimiters of this expression, e.g. the `{` and `}` of an
+    /// MLC, or `None` if it has none or isn't closed.
+    pub fn delimiters(&self) -> Option<(&'a MythicToken, &'a MythicToken)> {
+        match self {
+            NodeRef::MlcContainer(expr) => Some((&expr.left_brace, &expr.right_brace)),
+            NodeRef::InlineSkill(expr) => Some((
+                &expr.left_square_bracket,
+                expr.right_square_bracket.as_ref()?,
+            )),
+            NodeRef::Placeholder(expr) => {
+                Some((&expr.left_angle_bracket, expr.right_angle_bracket.as_ref()?))
+            }
+            _ => None,
+        }
+    }
+    /// Returns the most specific expression enclosing the given position, starting at this one.
+    /// Both ends of a range are inclusive, so a cursor right after an expression still resolves to it.
+    pub fn node_at(self, position: &CustomPosition) -> Option<NodeRef<'a>> {
+        self.path_at(position).last().copied()
+    }
+    /// Returns every expression enclosing the given position, from this one to the most specific.
+    /// This is empty if the position is outside of this expression.
+    pub fn path_at(self, position: &CustomPosition) -> Vec<NodeRef<'a>> {
+        let range = match self.get_range() {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        if *position < range.start || range.end < *position {
+            return Vec::new();
+        }
+        let mut path = vec![self];
+        if let Some(child_path) = self
+            .children()
+            .into_iter()
+            .map(|child| child.path_at(position))
+            .find(|child_path| !child_path.is_empty())
+        {
+            path.extend(child_path);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mythic_parser::parse_skill_line;
+
+    use super::*;
+
+    #[test]
+    fn finds_the_mlc_value_at_a_position() {
+        let (skill_line, _) = parse_skill_line("damage{amount=5} @self").unwrap();
+        let node = skill_line.node_at(&CustomPosition::new(0, 14));
+        assert!(matches!(node, Some(NodeRef::MlcValue(value)) if value.value() == "5"));
+    }
+
+    #[test]
+    fn finds_the_mechanic_at_its_name() {
+        let (skill_line, _) = parse_skill_line("damage{amount=5} @self").unwrap();
+        let node = skill_line.node_at(&CustomPosition::new(0, 2));
+        assert!(matches!(
+            node,
+            Some(NodeRef::Mechanic(mechanic)) if mechanic.name().value() == "damage"
+        ));
+    }
+
+    #[test]
+    fn renders_skill_lines_back_to_source() {
+        let canonical = [
+            "damage{amount=5} @self",
+            "damage{amount=5;ignorearmor=true} @EIR{r=5} ~onAttack 50%",
+            "message{m=<caster.name>} @trigger ~onDamaged:FIRE",
+            "skill{s=[ - damage{amount=1} @self ]} @target",
+            "heal{amount=1} @self ?health{h=50}",
+        ];
+        for source in canonical {
+            let (skill_line, errors) = parse_skill_line(source).unwrap();
+            assert!(errors.is_empty(), "{}", source);
+            assert_eq!(skill_line.to_source(), source);
+        }
+    }
+
+    #[test]
+    fn renders_skill_lines_with_canonical_spacing() {
+        let (skill_line, _) = parse_skill_line("damage{amount=5}   @self    ~onAttack").unwrap();
+        assert_eq!(skill_line.to_source(), "damage{amount=5} @self ~onAttack");
+    }
+
+    #[test]
+    fn labels_expressions() {
+        let (skill_line, _) =
+            parse_skill_line("damage{amount=5} @self ~onAttack ?health{h=5} 0.5").unwrap();
+        assert_eq!(
+            NodeRef::Mechanic(skill_line.mechanic()).label(),
+            "mechanic: damage"
+        );
+        assert_eq!(skill_line.targeter().unwrap().label(), "targeter: @self");
+        assert_eq!(skill_line.trigger().unwrap().label(), "trigger: ~onAttack");
+        assert_eq!(
+            skill_line.conditions()[0].label(),
+            "condition: ?health{h=5}"
+        );
+        assert_eq!(skill_line.chance().unwrap().label(), "chance: 0.5");
+        assert_eq!(skill_line.mlcs()[0].label(), "mlc: amount");
+    }
+
+    #[test]
+    fn finds_metaskills_called_through_aliases() {
+        for source in ["ms{s=Boom}", "metaskill{mechanics=Boom}", "SKILL{S=Boom}"] {
+            let (skill_line, _) = parse_skill_line(source).unwrap();
+            let names = skill_line
+                .skill_references()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["Boom"], "{}", source);
+        }
+        let (skill_line, _) = parse_skill_line("message{m=Boom}").unwrap();
+        assert!(skill_line.skill_references().is_empty());
     }
 }
@@ -0,0 +1,289 @@
+use tower_lsp::lsp_types::{SemanticTokenModifier, SemanticTokenType};
+
+use crate::{
+    documents::{get_bitset_for_modifiers, get_index_for_type, ImCompleteSemanticToken},
+    utilities::positions_and_ranges::CustomRange,
+};
+
+use super::{
+    expressions::{
+        ExprTrait, MlcContainer, MlcValue, MlcValueContainer, MlcValueIdentifier, SkillLine,
+        TargeterName,
+    },
+    lexer::{MythicToken, TokenType},
+    mechanics::{deprecated_mechanic, find_attribute, mechanic, Attribute, AttributeType},
+    triggers,
+};
+
+/// How each part of an MLC is highlighted, e.g. `amount`, `=`, and `5` in `amount=5`.
+/// Keys are parameters rather than properties, so they stand out from the YAML keys around them.
+const MLC_KEY: SemanticTokenType = SemanticTokenType::PARAMETER;
+const MLC_OPERATOR: SemanticTokenType = SemanticTokenType::OPERATOR;
+const MLC_NUMBER: SemanticTokenType = SemanticTokenType::NUMBER;
+const MLC_BOOLEAN: SemanticTokenType = SemanticTokenType::KEYWORD;
+const MLC_STRING: SemanticTokenType = SemanticTokenType::STRING;
+const MLC_PLACEHOLDER: SemanticTokenType = SemanticTokenType::VARIABLE;
+const MLC_FORMAT_TAG: SemanticTokenType = SemanticTokenType::MACRO;
+/// Variables that are set, like `caster.foo` in `setvariable{var=caster.foo}`, are highlighted
+/// like the placeholders that read them, e.g. `<caster.var.foo>`, but marked as modified.
+const MLC_VARIABLE: SemanticTokenType = SemanticTokenType::VARIABLE;
+
+/// Creates a semantic token spanning from the start of `first` to the end of `last`.
+fn span(
+    first: &MythicToken,
+    last: &MythicToken,
+    token_type: SemanticTokenType,
+) -> ImCompleteSemanticToken {
+    ImCompleteSemanticToken {
+        start: first.start as usize,
+        length: last.current.saturating_sub(first.start) as usize,
+        token_type: get_index_for_type(token_type),
+        modifiers: 0,
+    }
+}
+
+/// Creates a semantic token spanning a range of the skill line's source.
+fn range_span(
+    range: &CustomRange,
+    source: &str,
+    token_type: SemanticTokenType,
+) -> ImCompleteSemanticToken {
+    ImCompleteSemanticToken {
+        start: range.start.to_offset(source) as usize,
+        length: range.length_saturating(source) as usize,
+        token_type: get_index_for_type(token_type),
+        modifiers: 0,
+    }
+}
+
+/// Highlights the keys and `=` of an MLC, and its values by their type.
+/// `attributes` are the known attributes of whatever the MLC belongs to, if any,
+/// so the variables it sets can be told apart from other values.
+/// The values of inline skills are highlighted separately.
+fn highlight_mlc(
+    mlc: &MlcContainer,
+    attributes: &[Attribute],
+    source: &str,
+    tokens: &mut Vec<ImCompleteSemanticToken>,
+) {
+    for mlc in mlc.mlcs() {
+        tokens.push(span(mlc.key(), mlc.key(), MLC_KEY));
+        tokens.push(span(mlc.equals(), mlc.equals(), MLC_OPERATOR));
+        let value = match mlc.value() {
+            MlcValueContainer::MlcValue(value) => value,
+            MlcValueContainer::InlineSkill(_) => continue,
+        };
+        let is_variable = find_attribute(attributes, &mlc.key_name())
+            .is_some_and(|attribute| attribute.type_ == AttributeType::Variable);
+        match value.get_range() {
+            Some(range) if is_variable && !value.has_placeholders() => {
+                let mut token = range_span(&range, source, MLC_VARIABLE);
+                token.modifiers = get_bitset_for_modifiers(&[SemanticTokenModifier::MODIFICATION]);
+                tokens.push(token);
+            }
+            _ => highlight_mlc_value(value, source, tokens),
+        }
+    }
+}
+
+/// Highlights numbers (including ranges like `1to5`), booleans, quoted strings, and placeholders,
+/// along with the MLCs inside placeholders.
+fn highlight_mlc_value(value: &MlcValue, source: &str, tokens: &mut Vec<ImCompleteSemanticToken>) {
+    let is_number = value.as_number().is_some() || value.as_number_range().is_some();
+    if is_number && !value.has_placeholders() {
+        if let Some(range) = value.get_range() {
+            tokens.push(range_span(&range, source, MLC_NUMBER));
+        }
+        return;
+    }
+    if value.as_bool().is_some() {
+        if let Some(range) = value.get_range() {
+            tokens.push(range_span(&range, source, MLC_BOOLEAN));
+        }
+        return;
+    }
+    for identifier in value.identifiers() {
+        match identifier {
+            MlcValueIdentifier::Identifiers(identifiers) => tokens.extend(
+                identifiers
+                    .iter()
+                    .filter(|token| token.type_ == TokenType::String)
+                    .map(|token| span(token, token, MLC_STRING)),
+            ),
+            MlcValueIdentifier::Placeholder(placeholder) => {
+                tokens.push(range_span(
+                    &placeholder.get_range(),
+                    source,
+                    MLC_PLACEHOLDER,
+                ));
+                for mlc in placeholder.parts().iter().filter_map(|part| part.mlc()) {
+                    highlight_mlc(mlc, &[], source, tokens);
+                }
+            }
+            MlcValueIdentifier::FormatTag(tag) => {
+                tokens.push(range_span(&tag.get_range(), source, MLC_FORMAT_TAG))
+            }
+        }
+    }
+}
+
+/// Returns the modifiers of a mechanic's name: deprecated mechanics are marked as such,
+/// and mechanics known to MythicMobs are part of its default library.
+fn mechanic_modifiers(name: &str) -> u32 {
+    let mut modifiers = Vec::new();
+    if deprecated_mechanic(name).is_some() {
+        modifiers.push(SemanticTokenModifier::DEPRECATED);
+    }
+    if mechanic(name).is_some() {
+        modifiers.push(SemanticTokenModifier::DEFAULT_LIBRARY);
+    }
+    get_bitset_for_modifiers(&modifiers)
+}
+
+/// Computes the semantic tokens of a skill line, including the skill lines of its inline skills.
+/// Token starts are offsets into the skill line's source, not the document.
+pub fn highlight(skill_line: &SkillLine) -> Vec<ImCompleteSemanticToken> {
+    let mut tokens = Vec::new();
+    let name = &skill_line.mechanic().name().tokens;
+    let source = name
+        .first()
+        .map(|token| token.source.clone())
+        .unwrap_or_default();
+    if let Some(mlc) = skill_line.mechanic().mlc() {
+        let info = mechanic(&skill_line.mechanic().name().value());
+        let attributes = info.map_or(&[][..], |info| &info.attributes);
+        highlight_mlc(mlc, attributes, &source, &mut tokens);
+    }
+    // only mechanics set variables
+    let mlcs = skill_line
+        .targeter()
+        .and_then(|targeter| targeter.mlc())
+        .into_iter()
+        .chain(
+            skill_line
+                .conditions()
+                .iter()
+                .filter_map(|condition| condition.mlc()),
+        );
+    for mlc in mlcs {
+        highlight_mlc(mlc, &[], &source, &mut tokens);
+    }
+    if let (Some(first), Some(last)) = (name.first(), name.last()) {
+        let mut token = span(first, last, SemanticTokenType::FUNCTION);
+        token.modifiers = mechanic_modifiers(&skill_line.mechanic().name().value());
+        tokens.push(token);
+    }
+    if let Some(targeter) = skill_line.targeter() {
+        match targeter.name() {
+            TargeterName::Identifier(name) => {
+                tokens.push(span(targeter.at(), name, SemanticTokenType::CLASS))
+            }
+            TargeterName::Placeholder(placeholder) => {
+                tokens.push(span(targeter.at(), targeter.at(), SemanticTokenType::CLASS));
+                tokens.push(range_span(
+                    &placeholder.get_range(),
+                    &source,
+                    SemanticTokenType::VARIABLE,
+                ));
+            }
+        }
+    }
+    if let Some(trigger) = skill_line.trigger() {
+        let last = trigger.name().tokens.last().unwrap_or(trigger.caret());
+        let mut token = span(trigger.caret(), last, SemanticTokenType::EVENT);
+        if triggers::trigger(&trigger.name().value()).is_some() {
+            token.modifiers = get_bitset_for_modifiers(&[SemanticTokenModifier::DEFAULT_LIBRARY]);
+        }
+        tokens.push(token);
+    }
+    for condition in skill_line.conditions() {
+        tokens.push(span(
+            condition.question_mark(),
+            condition.name(),
+            SemanticTokenType::METHOD,
+        ));
+    }
+    for inline_skill_line in skill_line.inline_skill_lines() {
+        tokens.extend(highlight(inline_skill_line));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mythic_parser::parse_skill_line, testing::TestServer};
+
+    use super::*;
+
+    #[test]
+    fn marks_deprecated_mechanics() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Skills:\n  - jsonMessage{m=hi} @self\n"),
+        );
+        let deprecated = get_bitset_for_modifiers(&[SemanticTokenModifier::DEPRECATED]);
+        let tokens = doc
+            .encoded_semantic_tokens
+            .iter()
+            .filter(|token| token.token_modifiers_bitset & deprecated != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].length, "jsonMessage".len() as u32);
+    }
+
+    #[test]
+    fn highlights_mlc_values_by_their_type() {
+        let source = "damage{amount=5;element=\"fire\"} @self";
+        let (skill_line, _) = parse_skill_line(source).unwrap();
+        let tokens = highlight(&skill_line);
+        let text_of = |token_type| {
+            let token_type = get_index_for_type(token_type);
+            tokens
+                .iter()
+                .filter(|token| token.token_type == token_type)
+                .map(|token| &source[token.start..token.start + token.length])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(text_of(SemanticTokenType::NUMBER), vec!["5"]);
+        assert_eq!(text_of(SemanticTokenType::STRING), vec!["\"fire\""]);
+    }
+
+    #[test]
+    fn highlights_mlc_keys_and_operators() {
+        let source = "damage{amount=5}";
+        let (skill_line, _) = parse_skill_line(source).unwrap();
+        let tokens = highlight(&skill_line)
+            .into_iter()
+            .map(|token| {
+                (
+                    &source[token.start..token.start + token.length],
+                    token.token_type,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(tokens.contains(&("amount", get_index_for_type(SemanticTokenType::PARAMETER))));
+        assert!(tokens.contains(&("=", get_index_for_type(SemanticTokenType::OPERATOR))));
+        assert!(tokens.contains(&("5", get_index_for_type(SemanticTokenType::NUMBER))));
+    }
+
+    #[test]
+    fn highlights_set_and_read_variables() {
+        let variables = |source: &str| {
+            let (skill_line, _) = parse_skill_line(source).unwrap();
+            highlight(&skill_line)
+                .into_iter()
+                .filter(|token| token.token_type == get_index_for_type(SemanticTokenType::VARIABLE))
+                .map(|token| source[token.start..token.start + token.length].to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            variables("setvariable{var=caster.foo;val=1} @self"),
+            vec!["caster.foo"]
+        );
+        assert_eq!(
+            variables("message{m=<caster.var.foo>} @self"),
+            vec!["<caster.var.foo>"]
+        );
+    }
+}
@@ -10,26 +10,69 @@ pub enum TokenType {
     RightBrace,
     Semicolon,
     Equal,
+    EqualEqual,
     Dash,
+    Plus,
     At,
     Tilde,
     Question,
     Exclamation,
     Colon,
     LessThan,
+    LessEqual,
     GreaterThan,
+    GreaterEqual,
     Dot,
     Percent,
+    Star,
+    Hash,
+    Slash,
     Identifier,
     String,
     Number,
     Space,
+    /// A character the scanner doesn't recognize. It's kept so the text around it stays intact.
+    Unknown,
     Eof,
 }
 
-fn max_length(values: &[&str]) -> usize {
-    values.iter().map(|&s| s.len()).max().unwrap_or(0)
+impl TokenType {
+    /// Describes what a token of this type looks like, e.g. for "expected ..." diagnostics.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TokenType::LeftSquareBracket => "'['",
+            TokenType::RightSquareBracket => "']'",
+            TokenType::LeftBrace => "'{'",
+            TokenType::RightBrace => "'}'",
+            TokenType::Semicolon => "';'",
+            TokenType::Equal => "'='",
+            TokenType::EqualEqual => "'=='",
+            TokenType::Dash => "'-'",
+            TokenType::Plus => "'+'",
+            TokenType::At => "'@'",
+            TokenType::Tilde => "'~'",
+            TokenType::Question => "'?'",
+            TokenType::Exclamation => "'!'",
+            TokenType::Colon => "':'",
+            TokenType::LessThan => "'<'",
+            TokenType::LessEqual => "'<='",
+            TokenType::GreaterThan => "'>'",
+            TokenType::GreaterEqual => "'>='",
+            TokenType::Dot => "'.'",
+            TokenType::Percent => "'%'",
+            TokenType::Star => "'*'",
+            TokenType::Hash => "'#'",
+            TokenType::Slash => "'/'",
+            TokenType::Identifier => "a name",
+            TokenType::String => "a string",
+            TokenType::Number => "a number",
+            TokenType::Space => "a space",
+            TokenType::Unknown => "an unknown character",
+            TokenType::Eof => "the end of the skill line",
+        }
+    }
 }
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Hash)]
 pub struct MythicToken {
     pub source: String,
@@ -72,6 +115,39 @@ impl MythicToken {
             CustomPosition::from_offset(self.current, &self.source),
         )
     }
+
+    /// Returns the value of a `true` or `false` identifier, ignoring case like MythicMobs does.
+    /// Booleans are scanned as identifiers, since they're also valid names, e.g. of a variable.
+    pub fn as_bool(&self) -> Option<bool> {
+        if self.type_ != TokenType::Identifier {
+            return None;
+        }
+        let lexeme = self.lexeme.as_deref()?;
+        if lexeme.eq_ignore_ascii_case("true") {
+            Some(true)
+        } else if lexeme.eq_ignore_ascii_case("false") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Renders tokens one per line with their type, lexeme, and range, for debugging the scanner.
+/// e.g. `Identifier "a" Range(Position(0, 0), Position(0, 1))`.
+pub fn dump_tokens(tokens: &[MythicToken]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            format!(
+                "{:?} {:?} {}",
+                token.type_,
+                token.lexeme.as_deref().unwrap_or_default(),
+                token.get_range()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub struct MythicScanner {
@@ -80,6 +156,8 @@ pub struct MythicScanner {
     start: u32,
     current: u32,
     line: u32,
+    /// Errors found while scanning, e.g. unexpected characters.
+    errors: Vec<SyntaxError>,
 }
 
 impl MythicScanner {
@@ -90,13 +168,15 @@ impl MythicScanner {
             start: 0,
             current: 0,
             line: 1,
+            errors: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<MythicToken>, SyntaxError> {
+    /// Scans the whole source. Errors don't stop the scanner, they're collected in [`Self::errors`].
+    pub fn scan_tokens(&mut self) -> Vec<MythicToken> {
         while !self.is_at_end() {
             self.start = self.current;
-            self.scan_token()?;
+            self.scan_token();
         }
         self.tokens.push(MythicToken::new(
             self.source.clone(),
@@ -107,10 +187,15 @@ impl MythicScanner {
             self.start,
             self.current,
         ));
-        Ok(self.tokens.clone())
+        self.tokens.clone()
+    }
+
+    /// Returns the errors found while scanning so far.
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
     }
 
-    fn scan_token(&mut self) -> Result<(), SyntaxError> {
+    fn scan_token(&mut self) {
         let c = self.advance();
         match c {
             '[' => self.add_token(TokenType::LeftSquareBracket, None),
@@ -118,49 +203,57 @@ impl MythicScanner {
             '{' => self.add_token(TokenType::LeftBrace, None),
             '}' => self.add_token(TokenType::RightBrace, None),
             ';' => self.add_token(TokenType::Semicolon, None),
-            '=' => self.add_token(TokenType::Equal, None),
+            '=' => self.two_char_token('=', TokenType::EqualEqual, TokenType::Equal),
             '-' => self.add_token(TokenType::Dash, None),
+            '+' => self.add_token(TokenType::Plus, None),
             '@' => self.add_token(TokenType::At, None),
             '~' => self.add_token(TokenType::Tilde, None),
             '?' => self.add_token(TokenType::Question, None),
             '!' => self.add_token(TokenType::Exclamation, None),
             ':' => self.add_token(TokenType::Colon, None),
-            '<' => self.add_token(TokenType::LessThan, None),
-            '>' => self.add_token(TokenType::GreaterThan, None),
+            '<' => self.two_char_token('=', TokenType::LessEqual, TokenType::LessThan),
+            '>' => self.two_char_token('=', TokenType::GreaterEqual, TokenType::GreaterThan),
             '.' => self.add_token(TokenType::Dot, None),
             '%' => self.add_token(TokenType::Percent, None),
+            '*' => self.add_token(TokenType::Star, None),
+            '#' => self.add_token(TokenType::Hash, None),
+            '/' => self.add_token(TokenType::Slash, None),
             ' ' => self.add_token(TokenType::Space, None),
             '\r' => (),
             '\t' => (),
             '\n' => self.line += 1,
-            '"' => {
-                self.string('"')?;
-            }
-            '\'' => {
-                self.string('\'')?;
-            }
+            '"' => self.string('"'),
+            '\'' => self.string('\''),
             _ => {
                 if c.is_ascii_digit() {
-                    self.number()?;
+                    self.number();
                 } else if c.is_alphabetic() || c == '_' {
-                    self.identifier()?;
+                    self.identifier();
                 } else {
-                    return Err(SyntaxError::new(
+                    self.errors.push(SyntaxError::new(
                         self.get_range(),
                         format!("Unexpected character: {}", c),
                     ));
+                    self.add_token(TokenType::Unknown, None);
                 }
-
-                return Err(SyntaxError::new(
-                    self.get_range(),
-                    format!("Unexpected character: {}", c),
-                ));
             }
         };
-        Ok(())
     }
 
-    fn number(&mut self) -> Result<(), SyntaxError> {
+    /// Adds a two-character operator like `>=` if the next character is `second`,
+    /// or the single-character one otherwise.
+    fn two_char_token(&mut self, second: char, double: TokenType, single: TokenType) {
+        if self.peek() == second {
+            self.advance();
+            self.add_token(double, None);
+        } else {
+            self.add_token(single, None);
+        }
+    }
+
+    /// Scans a number with at most one decimal point, so `3.5.2` is `3.5`, `.`, and `2`.
+    /// A number directly followed by letters, like `3rd`, is scanned as a single identifier instead.
+    fn number(&mut self) {
         while self.peek().is_ascii_digit() {
             self.advance();
         }
@@ -170,59 +263,57 @@ impl MythicScanner {
                 self.advance();
             }
         }
+        if self.peek().is_alphabetic() || self.peek() == '_' {
+            return self.identifier();
+        }
         let value = self.source[self.start as usize..self.current as usize].to_string();
         self.add_token(TokenType::Number, Some(&value));
-        Ok(())
     }
 
-    fn identifier(&mut self) -> Result<(), SyntaxError> {
+    fn identifier(&mut self) {
         while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
         let value = self.source[self.start as usize..self.current as usize].to_string();
         self.add_token(TokenType::Identifier, Some(&value));
-        Ok(())
     }
 
-    fn string(&mut self, end: char) -> Result<(), SyntaxError> {
+    /// Scans a quoted string. An unterminated string runs to the end of the source.
+    fn string(&mut self, end: char) {
         while self.peek() != end && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
             }
             self.advance();
         }
-        self.advance();
         if self.is_at_end() {
-            return Err(SyntaxError::new(
+            self.errors.push(SyntaxError::new(
                 self.get_range(),
                 "Unterminated string.".to_string(),
             ));
+            let value = self.source[(self.start + 1) as usize..self.current as usize].to_string();
+            self.add_token(TokenType::String, Some(&value));
+            return;
         }
+        // the closing quote
+        self.advance();
         let value = self.source[(self.start + 1) as usize..(self.current - 1) as usize].to_string();
         self.add_token(TokenType::String, Some(&value));
-        Ok(())
     }
 
+    /// Offsets are in bytes, like the ranges built from them, so characters are read from
+    /// the rest of the source rather than by index, which would count characters instead.
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source
-                .chars()
-                .nth(self.current as usize)
-                .unwrap_or('\0')
-        }
+        self.rest().chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() as u32 {
-            '\0'
-        } else {
-            self.source
-                .chars()
-                .nth((self.current + 1) as usize)
-                .unwrap_or('\0')
-        }
+        self.rest().chars().nth(1).unwrap_or('\0')
+    }
+
+    /// Returns the source from the current offset on.
+    fn rest(&self) -> &str {
+        self.source.get(self.current as usize..).unwrap_or_default()
     }
 
     fn is_at_end(&self) -> bool {
@@ -244,15 +335,9 @@ impl MythicScanner {
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source
-            .chars()
-            .nth((self.current - 1) as usize)
-            .unwrap()
-    }
-
-    fn get_position(&self) -> CustomPosition {
-        CustomPosition::from_offset(self.current, &self.source)
+        let c = self.peek();
+        self.current += c.len_utf8() as u32;
+        c
     }
 
     fn get_range(&self) -> CustomRange {
@@ -262,3 +347,156 @@ impl MythicScanner {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::mythic_parser::{expressions::MlcValueContainer, parse_skill_line};
+
+    use super::*;
+
+    /// Scans a source, returning the type and lexeme of each token, without the final `Eof`.
+    fn scan(source: &str) -> Vec<(TokenType, String)> {
+        let mut scanner = MythicScanner::new(source.to_string());
+        let mut tokens = scanner.scan_tokens();
+        assert!(scanner.errors().is_empty());
+        tokens.pop();
+        tokens
+            .into_iter()
+            .map(|token| (token.type_, token.lexeme.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn scans_numbers_followed_by_letters_as_identifiers() {
+        assert_eq!(
+            scan("3rd"),
+            vec![(TokenType::Identifier, String::from("3rd"))]
+        );
+    }
+
+    #[test]
+    fn scans_decimal_numbers() {
+        assert_eq!(scan("3.5"), vec![(TokenType::Number, String::from("3.5"))]);
+        assert_eq!(
+            scan("3.5.2"),
+            vec![
+                (TokenType::Number, String::from("3.5")),
+                (TokenType::Dot, String::from(".")),
+                (TokenType::Number, String::from("2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn scans_and_parses_stars() {
+        let source = "effect:particles{amount=*2}";
+        assert!(scan(source).contains(&(TokenType::Star, String::from("*"))));
+        let (_, errors) = parse_skill_line(source).unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn collects_every_unexpected_character() {
+        let mut scanner = MythicScanner::new(String::from("damage{a=1`}\nheal{a=2`}"));
+        let tokens = scanner.scan_tokens();
+        let lines = scanner
+            .errors()
+            .iter()
+            .map(|error| error.range.start.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![0, 1]);
+        assert_eq!(tokens.last().map(|token| token.type_), Some(TokenType::Eof));
+        assert!(tokens
+            .iter()
+            .any(|token| token.lexeme.as_deref() == Some("heal")));
+    }
+
+    #[test]
+    fn dumps_one_line_per_token() {
+        let tokens = MythicScanner::new(String::from("a=5")).scan_tokens();
+        let dump = dump_tokens(&tokens);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            "Identifier \"a\" Range(Position(0, 0), Position(0, 1))"
+        );
+        assert_eq!(
+            lines[2],
+            "Number \"5\" Range(Position(0, 2), Position(0, 3))"
+        );
+    }
+
+    #[test]
+    fn recognizes_booleans_ignoring_case() {
+        let as_bool =
+            |source: &str| MythicScanner::new(source.to_string()).scan_tokens()[0].as_bool();
+        assert_eq!(as_bool("true"), Some(true));
+        assert_eq!(as_bool("TRUE"), Some(true));
+        assert_eq!(as_bool("false"), Some(false));
+        assert_eq!(as_bool("truthy"), None);
+    }
+
+    #[test]
+    fn scans_and_parses_signed_numbers() {
+        let source = "dir{x=-1.5;y=+2}";
+        let tokens = scan(source);
+        assert!(tokens.contains(&(TokenType::Dash, String::from("-"))));
+        assert!(tokens.contains(&(TokenType::Plus, String::from("+"))));
+        let (skill_line, errors) = parse_skill_line(source).unwrap();
+        assert!(errors.is_empty());
+        let numbers = skill_line
+            .mlcs()
+            .iter()
+            .map(|mlc| match mlc.value() {
+                MlcValueContainer::MlcValue(value) => value.as_number(),
+                MlcValueContainer::InlineSkill(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, vec![Some(-1.5), Some(2.0)]);
+    }
+
+    #[test]
+    fn scans_two_character_operators() {
+        assert_eq!(
+            scan(">=50"),
+            vec![
+                (TokenType::GreaterEqual, String::from(">=")),
+                (TokenType::Number, String::from("50")),
+            ]
+        );
+        assert_eq!(
+            scan("<=10"),
+            vec![
+                (TokenType::LessEqual, String::from("<=")),
+                (TokenType::Number, String::from("10")),
+            ]
+        );
+        assert_eq!(scan("==")[0].0, TokenType::EqualEqual);
+    }
+
+    #[test]
+    fn scans_numbers_after_multi_byte_characters() {
+        let tokens = scan("\"é\" 1.5");
+        assert_eq!(
+            tokens.last(),
+            Some(&(TokenType::Number, String::from("1.5")))
+        );
+        // the decimal point is only read when a digit follows it, even at the end
+        assert_eq!(
+            scan("\"é\" 1.").last(),
+            Some(&(TokenType::Dot, String::from(".")))
+        );
+    }
+
+    #[test]
+    fn scans_and_parses_slashes_in_commands() {
+        assert_eq!(scan("/say")[0], (TokenType::Slash, String::from("/")));
+        let (skill_line, errors) = parse_skill_line("command{c=/say hi} @self").unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        match skill_line.mlcs()[0].value() {
+            MlcValueContainer::MlcValue(value) => assert_eq!(value.to_source(), "/say hi"),
+            MlcValueContainer::InlineSkill(_) => panic!("expected a plain value"),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Deserialize;
+
+use super::catalog::{catalog, has_name};
+
+/// The type of value a mechanic attribute expects.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    /// A number, or a range of numbers like `1to5`.
+    Number,
+    Boolean,
+    String,
+    /// The name of a variable that's set, e.g. `caster.foo` in `setvariable{var=caster.foo}`.
+    Variable,
+    /// The name of a metaskill to run, e.g. `Other` in `skill{s=Other}`.
+    Metaskill,
+    /// One of the given values, ignoring case.
+    Enum(Vec<String>),
+}
+
+impl AttributeType {
+    /// Describes the expected value, e.g. for diagnostics.
+    pub fn description(&self) -> String {
+        match self {
+            AttributeType::Number => String::from("a number"),
+            AttributeType::Boolean => String::from("true or false"),
+            AttributeType::String => String::from("a string"),
+            AttributeType::Variable => String::from("a variable name"),
+            AttributeType::Metaskill => String::from("a metaskill name"),
+            AttributeType::Enum(values) => format!("one of {}", values.join(", ")),
+        }
+    }
+}
+
+/// The smallest value a number attribute accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Minimum {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl Minimum {
+    pub fn allows(&self, value: f64) -> bool {
+        match self {
+            Minimum::Inclusive(minimum) => value >= *minimum,
+            Minimum::Exclusive(minimum) => value > *minimum,
+        }
+    }
+    /// Describes the allowed values, e.g. for diagnostics.
+    pub fn description(&self) -> String {
+        match self {
+            Minimum::Inclusive(minimum) => format!("at least {}", minimum),
+            Minimum::Exclusive(minimum) => format!("greater than {}", minimum),
+        }
+    }
+}
+
+/// An attribute of a mechanic or targeter, e.g. `amount` in `damage{amount=5}`.
+#[derive(Debug, Deserialize)]
+pub struct Attribute {
+    /// The attribute's name followed by its aliases.
+    pub names: Vec<String>,
+    #[serde(rename = "type")]
+    pub type_: AttributeType,
+    /// The value used when the attribute is left out, if there is one.
+    pub default: Option<String>,
+    /// The smallest value allowed, for number attributes that have one.
+    pub minimum: Option<Minimum>,
+}
+
+/// Looks up an attribute by name or alias, ignoring case like MythicMobs does.
+pub fn find_attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attributes
+        .iter()
+        .find(|attribute| has_name(&attribute.names, name))
+}
+
+/// A known MythicMobs mechanic, as listed in the [`catalog`].
+#[derive(Debug, Deserialize)]
+pub struct Mechanic {
+    /// The mechanic's name followed by its aliases.
+    pub names: Vec<String>,
+    pub description: Option<String>,
+    /// Whether the mechanic does nothing without a targeter, unless one is inherited.
+    #[serde(default)]
+    pub requires_targeter: bool,
+    /// The mechanic to use instead, if MythicMobs still accepts this one but it's been superseded.
+    pub deprecated: Option<String>,
+    #[serde(default)]
+    pub attributes: Vec<Attribute>,
+}
+
+impl Mechanic {
+    /// Looks up an attribute by name or alias, ignoring case like MythicMobs does.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        find_attribute(&self.attributes, name)
+    }
+}
+
+/// Looks up a mechanic by name or alias, ignoring case like MythicMobs does.
+pub fn mechanic(name: &str) -> Option<&'static Mechanic> {
+    catalog()
+        .mechanics
+        .iter()
+        .find(|mechanic| has_name(&mechanic.names, name))
+}
+
+/// Looks up a deprecated mechanic by name or alias, ignoring case like MythicMobs does.
+pub fn deprecated_mechanic(name: &str) -> Option<&'static Mechanic> {
+    mechanic(name).filter(|mechanic| mechanic.deprecated.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_mechanics() {
+        let damage = mechanic("damage").unwrap();
+        assert!(damage.attribute("amount").is_some());
+        assert!(damage.requires_targeter);
+        for name in ["heal", "message", "teleport", "skill"] {
+            assert!(mechanic(name).is_some(), "{}", name);
+        }
+        assert!(mechanic("notAMechanic").is_none());
+    }
+}
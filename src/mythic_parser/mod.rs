@@ -1,3 +1,31 @@
+use crate::errors::error_registry::Error;
+
+use self::{expressions::SkillLine, lexer::MythicScanner, parser::Parser};
+
+pub mod catalog;
+pub mod conditions;
 pub mod expressions;
+pub mod highlighting;
 pub mod lexer;
+pub mod mechanics;
 pub mod parser;
+pub mod placeholders;
+pub mod targeters;
+pub mod triggers;
+pub mod validation;
+
+/// Scans and parses a single skill line, e.g. `damage{amount=5} @target ~onAttack`.
+/// Along with the skill line, returns the errors the scanner and parser recovered from.
+pub fn parse_skill_line(source: &str) -> Result<(SkillLine, Vec<Error>), Error> {
+    let mut scanner = MythicScanner::new(source.to_string());
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    let skill_line = parser.parse()?;
+    let errors = scanner
+        .errors()
+        .iter()
+        .map(|error| error.to_error())
+        .chain(parser.errors().iter().cloned())
+        .collect();
+    Ok((skill_line, errors))
+}
@@ -1,37 +1,111 @@
 use crate::errors::error_registry::{
-    Error, SyntaxError, TargeterAlreadyDefinedError, TriggerAlreadyDefinedError,
+    DuplicateConditionMarkerError, Error, NestingTooDeepError, RelatedInformation,
+    SpaceAroundEqualsError, SyntaxError, TargeterAlreadyDefinedError, TriggerAlreadyDefinedError,
+    UnexpectedEofError, UnterminatedInlineSkillError, UnterminatedPlaceholderError,
 };
 
+use crate::utilities::positions_and_ranges::CustomRange;
+
 use super::{
     expressions::{
-        Chance, GenericNameAndMlc, GenericString, HealthModifier, HealthModifierValue,
-        HealthModifierValueOrRange, InlineCondition, Mlc, MlcContainer, MlcValue,
-        MlcValueContainer, MlcValueIdentifier, Placeholder, SkillLine, Targeter, Trigger, InlineSkill, InlineSkillSkillContainer, ExprTrait,
+        Chance, ExprTrait, FormatTag, GenericNameAndMlc, GenericString, HealthModifier,
+        HealthModifierValue, HealthModifierValueOrRange, InlineCondition, InlineSkill,
+        InlineSkillSkillContainer, Mlc, MlcContainer, MlcValue, MlcValueContainer,
+        MlcValueIdentifier, Placeholder, SkillLine, Targeter, TargeterName, Trigger,
     },
     lexer::{MythicToken, TokenType},
 };
 
+/// MiniMessage tags that can follow a `<` in a value, e.g. `<gradient:#ff0000:#00ff00>`.
+/// These are formatting, not placeholders, even though both use angle brackets.
+const FORMAT_TAGS: &[&str] = &[
+    "black",
+    "dark_blue",
+    "dark_green",
+    "dark_aqua",
+    "dark_red",
+    "dark_purple",
+    "gold",
+    "gray",
+    "grey",
+    "dark_gray",
+    "dark_grey",
+    "blue",
+    "green",
+    "aqua",
+    "red",
+    "light_purple",
+    "yellow",
+    "white",
+    "color",
+    "colour",
+    "gradient",
+    "rainbow",
+    "transition",
+    "bold",
+    "italic",
+    "em",
+    "underlined",
+    "strikethrough",
+    "st",
+    "obfuscated",
+    "obf",
+    "reset",
+    "newline",
+    "br",
+    "hover",
+    "click",
+    "key",
+    "lang",
+    "insert",
+    "font",
+    "shadow",
+];
+
+/// The default maximum number of nested MLCs, e.g. through inline skills or placeholders.
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
 pub struct Parser {
     current: usize,
     tokens: Vec<MythicToken>,
-    result: Vec<MythicToken>,
-    source: String,
+    /// The current number of nested MLCs.
+    depth: usize,
+    /// The maximum number of nested MLCs before parsing fails, to avoid overflowing the stack.
+    max_depth: usize,
+    /// Errors that the parser recovered from, e.g. a placeholder missing its `>`.
+    errors: Vec<Error>,
 }
 
 impl Parser {
-    pub fn new(result: Vec<MythicToken>, source: String) -> Self {
+    pub fn new(tokens: Vec<MythicToken>) -> Self {
+        Self::with_max_depth(tokens, DEFAULT_MAX_DEPTH)
+    }
+    pub fn with_max_depth(tokens: Vec<MythicToken>, max_depth: usize) -> Self {
         Self {
             current: 0,
-            tokens: result,
-            result: Vec::new(),
-            source,
+            tokens,
+            depth: 0,
+            max_depth,
+            errors: Vec::new(),
         }
     }
+    /// Returns the errors the parser recovered from so far.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+    /// Parses a top-level skill line, e.g. the value of a YAML `Skills` list item.
+    /// The YAML dash is normally not part of the source, but a leading `- ` is skipped if present.
+    /// Dashes in inline skills are required and handled by [`Self::inline_skill`] instead.
     pub fn parse(&mut self) -> Result<SkillLine, Error> {
+        self.consume_whitespace();
+        if self.matches(TokenType::Dash) {
+            self.consume_whitespace();
+        }
         self.skill_line(Vec::new())
     }
+    /// Parses a skill line without a leading dash, stopping at any of `exit_types`.
     fn skill_line(&mut self, exit_types: Vec<TokenType>) -> Result<SkillLine, Error> {
-        let mechanic = self.generic_name_and_mlc()?;
+        let mechanic = self.generic_name_and_mlc(vec![TokenType::LeftBrace, TokenType::Space])?;
         let mut targeter: Option<Box<Targeter>> = None;
         let mut trigger: Option<Box<Trigger>> = None;
         let mut conditions: Vec<InlineCondition> = Vec::new();
@@ -44,27 +118,47 @@ impl Parser {
                 break;
             }
             if self.match_all(vec![TokenType::At]) {
-                if targeter.is_some() {
-                    return Err(
-                        TargeterAlreadyDefinedError::new(targeter.unwrap().get_range()).to_error(),
-                    );
+                let new_targeter = self.targeter()?;
+                if let Some(targeter) = targeter {
+                    return Err(TargeterAlreadyDefinedError::new(new_targeter.get_range())
+                        .to_error()
+                        .with_related_information(RelatedInformation::new(
+                            targeter.get_range(),
+                            String::from("The targeter is first defined here."),
+                        )));
                 }
-                targeter = Some(Box::new(self.targeter()?));
+                targeter = Some(Box::new(new_targeter));
             } else if self.match_all(vec![TokenType::Tilde]) {
-                if trigger.is_some() {
-                    return Err(
-                        TriggerAlreadyDefinedError::new(targeter.unwrap().get_range()).to_error(),
-                    );
+                let new_trigger = self.trigger()?;
+                if let Some(trigger) = trigger {
+                    return Err(TriggerAlreadyDefinedError::new(new_trigger.get_range())
+                        .to_error()
+                        .with_related_information(RelatedInformation::new(
+                            trigger.get_range(),
+                            String::from("The trigger is first defined here."),
+                        )));
                 }
-                trigger = Some(Box::new(self.trigger()?));
+                trigger = Some(Box::new(new_trigger));
             } else if self.match_all(vec![TokenType::Question]) {
                 conditions.push(self.inline_condition()?);
-            } else if self.match_all(vec![TokenType::Percent]) {
-                chance = Some(Box::new(Chance::new(self.previous().to_owned())));
+            } else if self.match_all(vec![TokenType::Number]) {
+                chance = Some(Box::new(self.chance(None)));
+            } else if self.check(TokenType::Dash)
+                && self
+                    .tokens
+                    .get(self.current + 1)
+                    .is_some_and(|token| token.type_ == TokenType::Number)
+            {
+                let minus = self.advance().to_owned();
+                self.advance();
+                chance = Some(Box::new(self.chance(Some(minus))));
             } else if self.check_any(vec![
                 TokenType::LessThan,
+                TokenType::LessEqual,
                 TokenType::GreaterThan,
+                TokenType::GreaterEqual,
                 TokenType::Equal,
+                TokenType::EqualEqual,
             ]) {
                 health_modifier = Some(Box::new(self.health_modifier()?));
             } else if self.check_any(exit_types) {
@@ -87,12 +181,10 @@ impl Parser {
             health_modifier,
         ))
     }
-    fn generic_name_and_mlc(&mut self) -> Result<GenericNameAndMlc, Error> {
-        let name = self.generic_string(
-            vec![TokenType::LeftBrace, TokenType::Space],
-            Some(String::from("Expected mechanic name!")),
-        )?;
-        if name.tokens.len() == 0 {
+    /// Parses a name followed by an optional MLC. The name ends at any of `end`, or at a `{`.
+    fn generic_name_and_mlc(&mut self, end: Vec<TokenType>) -> Result<GenericNameAndMlc, Error> {
+        let name = self.generic_string(end, Some(String::from("Expected mechanic name!")))?;
+        if name.tokens.is_empty() {
             return Err(SyntaxError::new(
                 self.peek().get_range(),
                 String::from("Expected mechanic name!"),
@@ -106,12 +198,18 @@ impl Parser {
             Ok(GenericNameAndMlc::new(name, None))
         }
     }
+    /// Parses a targeter after its `@`. The name is a single identifier or a placeholder,
+    /// and the MLC is optional, so `@Self`, `@EIR{r=5}`, and `@<skill.mytarget>` are all valid.
     fn targeter(&mut self) -> Result<Targeter, Error> {
         let at = self.previous().to_owned();
-        let name = self.consume(
-            TokenType::Identifier,
-            Some(String::from("Expected targeter name!")),
-        )?;
+        let name = if self.matches(TokenType::LessThan) {
+            TargeterName::Placeholder(self.placeholder()?)
+        } else {
+            TargeterName::Identifier(self.consume(
+                TokenType::Identifier,
+                Some(String::from("Expected targeter name!")),
+            )?)
+        };
         if self.check(TokenType::LeftBrace) {
             let mlc = self.mlc()?;
             Ok(Targeter::new(at, name, Some(Box::new(mlc))))
@@ -122,7 +220,7 @@ impl Parser {
     fn trigger(&mut self) -> Result<Trigger, Error> {
         let caret = self.previous().to_owned();
         let name = self.generic_string(
-            vec![TokenType::LeftBrace, TokenType::Space],
+            vec![TokenType::LeftBrace, TokenType::Space, TokenType::Colon],
             Some(String::from("Expected a trigger name!")),
         )?;
         let arg: Option<GenericString>;
@@ -137,38 +235,31 @@ impl Parser {
             colon = None;
             arg = None;
         }
-        Ok(Trigger::new(caret, name, colon, arg.map(|x| Box::new(x))))
+        Ok(Trigger::new(caret, name, colon, arg.map(Box::new)))
     }
     fn inline_condition(&mut self) -> Result<InlineCondition, Error> {
         let question = self.previous().to_owned();
-        let mut not = false;
-        let mut trigger = false;
-        let exclam: Option<MythicToken> = None;
+        let mut exclam: Option<MythicToken> = None;
         let mut tilde: Option<MythicToken> = None;
-        for _ in 0..2 {
-            if self.check_any(vec![TokenType::Exclamation, TokenType::Tilde]) {
-                if self.match_all(vec![TokenType::Exclamation]) {
-                    if exclam.is_some() {
-                        return Err(SyntaxError::new(
-                            self.peek().get_range(),
-                            String::from("Duplicate inline condition trigger symbol!"),
-                        )
-                        .to_error());
-                    }
-                    not = true;
-                }
-                if self.match_all(vec![TokenType::Tilde]) {
-                    if tilde.is_some() {
-                        return Err(SyntaxError::new(
-                            self.peek().get_range(),
-                            String::from("Duplicate inline condition trigger symbol!"),
-                        )
-                        .to_error());
-                    }
-                    tilde = Some(self.previous().to_owned());
-                    trigger = true;
-                }
+        // `!` negates the condition and `~` checks the trigger instead of the target.
+        // They can come in either order, but each only once.
+        while self.check_any(vec![TokenType::Exclamation, TokenType::Tilde]) {
+            let marker = self.advance().to_owned();
+            let slot = match marker.type_ {
+                TokenType::Exclamation => &mut exclam,
+                _ => &mut tilde,
+            };
+            if slot.is_some() {
+                self.errors.push(
+                    DuplicateConditionMarkerError::new(
+                        marker.get_range(),
+                        marker.lexeme.clone().unwrap_or_default(),
+                    )
+                    .to_error(),
+                );
+                continue;
             }
+            *slot = Some(marker);
         }
         let name = self.consume(
             TokenType::Identifier,
@@ -181,12 +272,26 @@ impl Parser {
             Ok(InlineCondition::new(question, exclam, tilde, name, None))
         }
     }
+    /// Parses a chance, either as a fraction like `0.5` or as a percentage like `50%`.
+    /// The number has already been consumed, along with the `-` of a negative chance.
+    fn chance(&mut self, minus: Option<MythicToken>) -> Chance {
+        let value = self.previous().to_owned();
+        let percent = if self.matches(TokenType::Percent) {
+            Some(self.previous().to_owned())
+        } else {
+            None
+        };
+        Chance::new(minus, value, percent)
+    }
     fn health_modifier(&mut self) -> Result<HealthModifier, Error> {
         let operator = self.consume_any(
             vec![
                 TokenType::Equal,
+                TokenType::EqualEqual,
                 TokenType::GreaterThan,
+                TokenType::GreaterEqual,
                 TokenType::LessThan,
+                TokenType::LessEqual,
             ],
             Some(String::from("Expected health modifier operator!")),
         )?;
@@ -204,7 +309,7 @@ impl Parser {
                 Some(String::from("Expected second health modifier value!")),
             )?;
             let mut max_value = HealthModifierValue::Absolute(max.clone());
-            if self.check(TokenType::Percent) {
+            if self.matches(TokenType::Percent) {
                 max_value = HealthModifierValue::Percentage(max, self.previous().to_owned())
             }
             Ok(HealthModifier::new(
@@ -219,6 +324,9 @@ impl Parser {
         }
     }
 
+    /// Collects the tokens up to one of `end` as text, skipping over brackets.
+    /// Tokens like `!` are kept as text, e.g. in `~onSignal:hello!`,
+    /// as only [`Self::inline_condition`] reads `!` as a negation.
     fn generic_string(
         &mut self,
         end: Vec<TokenType>,
@@ -226,56 +334,78 @@ impl Parser {
     ) -> Result<GenericString, Error> {
         let start = self.current;
         while !self.check_any(end.clone()) && !self.is_at_end() {
-            if self.check(TokenType::LeftBrace) {
-                while !self.check(TokenType::RightBrace) {
-                    self.advance();
-                }
-            }
-            if self.check(TokenType::LeftSquareBracket) {
-                while !self.check(TokenType::RightSquareBracket) {
-                    self.advance();
-                }
+            if self.matches(TokenType::LeftBrace) {
+                self.skip_brackets(TokenType::LeftBrace, TokenType::RightBrace, "}")?;
+            } else if self.matches(TokenType::LeftSquareBracket) {
+                self.skip_brackets(
+                    TokenType::LeftSquareBracket,
+                    TokenType::RightSquareBracket,
+                    "]",
+                )?;
+            } else {
+                self.advance();
             }
-            self.advance();
         }
         let string = self.tokens[start..self.current].to_vec();
-        if string.len() == 0 && error.is_some() {
-            return Err(SyntaxError::new(self.peek().get_range(), error.unwrap()).to_error());
+        if let (true, Some(error)) = (string.is_empty(), error) {
+            return Err(SyntaxError::new(self.peek().get_range(), error).to_error());
         }
         Ok(GenericString::new(string))
     }
     fn mlc(&mut self) -> Result<MlcContainer, Error> {
+        // Every recursive path (inline skills, nested placeholders) goes through an MLC,
+        // so this is the only place the depth needs to be tracked.
+        if self.depth >= self.max_depth {
+            return Err(NestingTooDeepError::new(
+                self.peek().get_range(),
+                self.max_depth.to_string(),
+            )
+            .to_error());
+        }
+        self.depth += 1;
+        let mlc = self.mlc_contents();
+        self.depth -= 1;
+        mlc
+    }
+    fn mlc_contents(&mut self) -> Result<MlcContainer, Error> {
         let left_brace = self.consume(
             TokenType::LeftBrace,
             Some(String::from("Expected '{' before mlc!")),
         )?;
         let mut mlcs: Vec<Mlc> = vec![];
         loop {
-            let mut semicolon: Option<MythicToken> = None;
-            let previous_lexeme = (&self.previous()).lexeme.clone().unwrap_or(String::from(""));
-            if previous_lexeme == ";" {
-                semicolon = Some(self.previous().to_owned());
-            }
+            self.consume_whitespace();
+            // an empty MLC, or a trailing `;` like `{a=1; }`, which MythicMobs allows
             if self.check(TokenType::RightBrace) {
                 break;
             }
-            self.consume_whitespace();
             let key = self.consume(
                 TokenType::Identifier,
                 Some(String::from("Expected mlc key!")),
             )?;
             // self.completion_generic(vec![TokenType::Equal]);
+            // MythicMobs doesn't allow spaces around the `=`, but the intent is clear,
+            // so they're reported and skipped
+            let spaced = self.check(TokenType::Space);
+            if spaced {
+                self.consume_whitespace();
+            }
             let equals = self.consume(
                 TokenType::Equal,
                 Some(String::from("Expected '=' after mlc key!")),
             )?;
-            let value = self.mlc_value()?;
-            mlcs.push(Mlc::new(
-                key,
-                equals,
-                MlcValueContainer::MlcValue(value),
-                semicolon,
-            ));
+            if spaced || self.check(TokenType::Space) {
+                self.consume_whitespace();
+                let range = CustomRange::new(key.get_range().end, self.peek().get_range().start);
+                self.errors
+                    .push(SpaceAroundEqualsError::new(range).to_error());
+            }
+            let value = if self.match_all(vec![TokenType::LeftSquareBracket]) {
+                MlcValueContainer::InlineSkill(self.inline_skill()?)
+            } else {
+                MlcValueContainer::MlcValue(self.mlc_value()?)
+            };
+            mlcs.push(Mlc::new(key, equals, value));
             // self.completion_generic(vec![TokenType::Semicolon, TokenType::RightBrace]);
             self.consume_whitespace();
             // self.completion_generic(vec![TokenType::Semicolon, TokenType::RightBrace]);
@@ -290,6 +420,10 @@ impl Parser {
         )?;
         Ok(MlcContainer::new(left_brace, mlcs, right_brace))
     }
+    /// Parses an MLC value up to the next `;` or `}`, splitting out its placeholders.
+    /// Placeholders can have MLCs of their own, so this recurses for values like
+    /// `<skill.x{a=<caster.level>}>`.
+    /// Every other token is part of the value, e.g. the `!` in `message{m=Hello!}`.
     fn mlc_value(&mut self) -> Result<MlcValue, Error> {
         let mut parts: Vec<MlcValueIdentifier> = vec![];
         let mut start = self.current;
@@ -300,16 +434,20 @@ impl Parser {
                 parts.push(MlcValueIdentifier::Identifiers(
                     self.tokens[start..self.current - 1].to_vec(),
                 ));
-                parts.push(MlcValueIdentifier::Placeholder(self.placeholder()?));
-                start = self.current;
-            } else if self.match_all(vec![TokenType::LeftBrace]) {
-                while !self.match_all(vec![TokenType::RightBrace]) {
-                    self.advance();
-                }
-            } else if self.match_all(vec![TokenType::LeftSquareBracket]) {
-                while !self.match_all(vec![TokenType::RightSquareBracket]) {
-                    self.advance();
+                if self.is_format_tag() {
+                    parts.push(MlcValueIdentifier::FormatTag(self.format_tag()));
+                } else {
+                    parts.push(MlcValueIdentifier::Placeholder(self.placeholder()?));
                 }
+                start = self.current;
+            } else if self.matches(TokenType::LeftBrace) {
+                self.skip_brackets(TokenType::LeftBrace, TokenType::RightBrace, "}")?;
+            } else if self.matches(TokenType::LeftSquareBracket) {
+                self.skip_brackets(
+                    TokenType::LeftSquareBracket,
+                    TokenType::RightSquareBracket,
+                    "]",
+                )?;
             } else {
                 self.advance();
             }
@@ -319,31 +457,89 @@ impl Parser {
         ));
         Ok(MlcValue::new(parts))
     }
+    /// Whether the `<` that was just consumed starts a MiniMessage tag rather than a placeholder,
+    /// i.e. it's followed by a hex color like `#ff0000` or one of the [`FORMAT_TAGS`],
+    /// optionally closing it like `</gradient>`.
+    fn is_format_tag(&self) -> bool {
+        let mut token = self.peek();
+        if token.type_ == TokenType::Slash {
+            token = match self.tokens.get(self.current + 1) {
+                Some(token) => token,
+                None => return false,
+            };
+        }
+        match token.type_ {
+            TokenType::Hash => true,
+            TokenType::Identifier => token.lexeme.as_deref().is_some_and(|lexeme| {
+                FORMAT_TAGS
+                    .iter()
+                    .any(|tag| tag.eq_ignore_ascii_case(lexeme))
+            }),
+            _ => false,
+        }
+    }
+    /// Parses a MiniMessage tag up to its `>`. Its arguments aren't parsed any further.
+    fn format_tag(&mut self) -> FormatTag {
+        let left_angle_bracket = self.previous().to_owned();
+        let start = self.current;
+        while !self.check_any(vec![
+            TokenType::GreaterThan,
+            TokenType::GreaterEqual,
+            TokenType::Semicolon,
+            TokenType::RightBrace,
+        ]) && !self.is_at_end()
+        {
+            self.advance();
+        }
+        let contents = self.tokens[start..self.current].to_vec();
+        self.split_greater_equal();
+        let right_angle_bracket = if self.matches(TokenType::GreaterThan) {
+            Some(self.previous().to_owned())
+        } else {
+            self.errors.push(
+                SyntaxError::new(
+                    left_angle_bracket.get_range(),
+                    String::from("Expected '>' to close this tag!"),
+                )
+                .to_error(),
+            );
+            None
+        };
+        FormatTag::new(left_angle_bracket, contents, right_angle_bracket)
+    }
     fn placeholder(&mut self) -> Result<Placeholder, Error> {
-        let left_square_bracket = self.consume(
-            TokenType::LeftSquareBracket,
-            Some(String::from("Expected '[' before placeholder!")),
-        )?;
+        let left_angle_bracket = self.previous().to_owned();
+        // a placeholder can't contain these, so they end an unterminated one
+        let part_end = vec![
+            TokenType::LeftBrace,
+            TokenType::Dot,
+            TokenType::GreaterThan,
+            TokenType::GreaterEqual,
+            TokenType::RightBrace,
+            TokenType::Semicolon,
+            TokenType::Space,
+        ];
         let mut parts: Vec<GenericNameAndMlc> = vec![];
-        let mut dots: Vec<MythicToken> = vec![];
-        let part = self.generic_name_and_mlc()?;
+        let part = self.generic_name_and_mlc(part_end.clone())?;
         parts.push(part);
         // self.completion_generic(vec![TokenType::Dot, TokenType::GreaterThan]);
         while self.match_all(vec![TokenType::Dot]) && !self.is_at_end() {
-            dots.push(self.previous().to_owned());
-            let part = self.generic_name_and_mlc()?;
+            let part = self.generic_name_and_mlc(part_end.clone())?;
             parts.push(part);
             // self.completion_generic(vec![TokenType::Dot, TokenType::GreaterThan]);
         }
-        let right_square_bracket = self.consume(
-            TokenType::GreaterThan,
-            Some(String::from("Expected '>' after placeholder!")),
-        )?;
+        self.split_greater_equal();
+        let right_angle_bracket = if self.matches(TokenType::GreaterThan) {
+            Some(self.previous().to_owned())
+        } else {
+            self.errors
+                .push(UnterminatedPlaceholderError::new(left_angle_bracket.get_range()).to_error());
+            None
+        };
         Ok(Placeholder::new(
-            left_square_bracket,
+            left_angle_bracket,
             parts,
-            dots,
-            right_square_bracket,
+            right_angle_bracket,
         ))
     }
     // typescript:
@@ -370,43 +566,101 @@ impl Parser {
     //     const rightSquareBracket = this.#consume("RightSquareBracket", "Expected ']' after inline skill!");
     //     return new InlineSkillExpr(this, this.#currentPosition(), leftSquareBracket, dashesAndSkills, rightSquareBracket);
     // }
+    /// Parses an inline skill, e.g. `[ - damage{a=1} - heal{a=1} ]`.
+    /// An inline skill that's never closed fails at its `[`, as the MLC it's in can't be closed
+    /// either, and the end of the skill line is the closest place a `]` could be expected.
     fn inline_skill(&mut self) -> Result<InlineSkill, Error> {
         let left_square_bracket = &self.previous().clone();
         let mut dashes_and_skills: Vec<InlineSkillSkillContainer> = vec![];
-        while !self.check(TokenType::RightSquareBracket) && !self.is_at_end() {
+        // the `}` of the surrounding MLC ends an unterminated inline skill
+        while !self.check_any(vec![TokenType::RightSquareBracket, TokenType::RightBrace])
+            && !self.is_at_end()
+        {
             // self.completion_generic(vec![TokenType::Dash, TokenType::RightSquareBracket]);
             // optional whitespace
             let _ = &self.consume_whitespace();
+            if self.is_at_end() || self.check(TokenType::RightBrace) {
+                break;
+            }
             // self.completion_generic(vec![TokenType::Dash, TokenType::RightSquareBracket]);
             // dash
-            let dash = self.consume(
+            self.consume(
                 TokenType::Dash,
                 Some(String::from("Expected '-' after '['!")),
             )?;
             // optional whitespace
             self.consume_whitespace();
             // skill
-            let skill = self.skill_line(
-                vec![TokenType::RightSquareBracket, TokenType::Dash],
-            )?;
+            let skill = self.skill_line(vec![
+                TokenType::RightSquareBracket,
+                TokenType::Dash,
+                TokenType::RightBrace,
+            ])?;
             // optional whitespace
             self.consume_whitespace();
-            dashes_and_skills.push(InlineSkillSkillContainer::new(
-                dash,
-                skill,
-            ));
-        }
-        let right_square_bracket = self.consume(
-            TokenType::RightSquareBracket,
-            Some(String::from("Expected ']' after inline skill!")),
-        )?;
+            dashes_and_skills.push(InlineSkillSkillContainer::new(skill));
+        }
+        let right_square_bracket = if self.matches(TokenType::RightSquareBracket) {
+            Some(self.previous().to_owned())
+        } else {
+            self.errors.push(
+                UnterminatedInlineSkillError::new(left_square_bracket.get_range()).to_error(),
+            );
+            None
+        };
         Ok(InlineSkill::new(
             left_square_bracket.to_owned(),
             dashes_and_skills,
+            right_square_bracket,
         ))
     }
+    /// Skips past the bracket closing the one that was just consumed, including any nested brackets.
+    /// Fails at the opening bracket if it's never closed.
+    fn skip_brackets(
+        &mut self,
+        open: TokenType,
+        close: TokenType,
+        close_lexeme: &str,
+    ) -> Result<(), Error> {
+        let opening = self.previous().to_owned();
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(SyntaxError::new(
+                    opening.get_range(),
+                    format!("Expected '{}' to close this bracket!", close_lexeme),
+                )
+                .to_error());
+            }
+            let type_ = self.advance().type_;
+            if type_ == open {
+                depth += 1;
+            } else if type_ == close {
+                depth -= 1;
+            }
+        }
+        Ok(())
+    }
+    /// Splits a `>=` token into `>` and `=`, for when the `>` closes a placeholder or tag,
+    /// e.g. in `m=<caster.hp>=low`.
+    fn split_greater_equal(&mut self) {
+        if !self.check(TokenType::GreaterEqual) {
+            return;
+        }
+        let token = self.peek().to_owned();
+        let mut greater = token.clone();
+        greater.type_ = TokenType::GreaterThan;
+        greater.lexeme = Some(String::from(">"));
+        greater.current = token.start + 1;
+        let mut equal = token;
+        equal.type_ = TokenType::Equal;
+        equal.lexeme = Some(String::from("="));
+        equal.start += 1;
+        self.tokens
+            .splice(self.current..self.current + 1, [greater, equal]);
+    }
     fn consume_whitespace(&mut self) {
-        while !self.matches(TokenType::Space) {}
+        while self.matches(TokenType::Space) {}
     }
     fn matches(&mut self, type_: TokenType) -> bool {
         if self.is_at_end() {
@@ -446,13 +700,21 @@ impl Parser {
         types: Vec<TokenType>,
         error: Option<String>,
     ) -> Result<MythicToken, Error> {
-        for type_ in types {
-            if self.check(type_) {
+        for type_ in &types {
+            if self.check(*type_) {
                 return Ok(self.advance().to_owned());
             }
         }
-        if error.is_some() {
-            return Err(SyntaxError::new(self.peek().get_range(), error.unwrap()).to_error());
+        if self.is_at_end() {
+            let expected = types
+                .iter()
+                .map(|type_| type_.description())
+                .collect::<Vec<_>>()
+                .join(" or ");
+            return Err(self.unexpected_eof(expected));
+        }
+        if let Some(error) = error {
+            return Err(SyntaxError::new(self.peek().get_range(), error).to_error());
         }
         Err(SyntaxError::new(self.peek().get_range(), String::from("Unexpected token!")).to_error())
     }
@@ -460,11 +722,23 @@ impl Parser {
         if self.check(type_) {
             return Ok(self.advance().to_owned());
         }
-        if error.is_some() {
-            return Err(SyntaxError::new(self.peek().get_range(), error.unwrap()).to_error());
+        if self.is_at_end() {
+            return Err(self.unexpected_eof(type_.description().to_string()));
+        }
+        if let Some(error) = error {
+            return Err(SyntaxError::new(self.peek().get_range(), error).to_error());
         }
         Err(SyntaxError::new(self.peek().get_range(), String::from("Unexpected token!")).to_error())
     }
+    /// Creates an error for reaching the end of the skill line while expecting something else.
+    /// It's anchored right after the last token, as the end itself has no range.
+    fn unexpected_eof(&self, expected: String) -> Error {
+        let end = match self.current {
+            0 => self.peek().get_range().start,
+            _ => self.previous().get_range().end,
+        };
+        UnexpectedEofError::new(CustomRange::new(end, end), expected).to_error()
+    }
     fn advance(&mut self) -> &MythicToken {
         if !self.is_at_end() {
             self.current += 1;
@@ -481,3 +755,250 @@ impl Parser {
         &self.tokens[self.current - 1]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::mythic_parser::parse_skill_line;
+
+    use super::*;
+
+    #[test]
+    fn does_not_parse_placeholders_in_strings() {
+        let (skill_line, errors) =
+            parse_skill_line("message{m=\"<not a placeholder>\"} @self").unwrap();
+        assert!(errors.is_empty());
+        assert!(skill_line.placeholders().is_empty());
+    }
+
+    #[test]
+    fn reports_inline_skills_nested_too_deeply() {
+        let depth = DEFAULT_MAX_DEPTH + 8;
+        let source = format!(
+            "{}damage{{amount=1}}{}",
+            "skill{s=[ - ".repeat(depth),
+            " ]}".repeat(depth)
+        );
+        let error = parse_skill_line(&source).err().unwrap();
+        assert_eq!(error.code, "nesting_too_deep_error");
+    }
+
+    #[test]
+    fn parses_inline_skills_nested_within_the_limit() {
+        let depth = DEFAULT_MAX_DEPTH - 1;
+        let source = format!(
+            "{}damage{{amount=1}}{}",
+            "skill{s=[ - ".repeat(depth),
+            " ]}".repeat(depth)
+        );
+        let (_, errors) = parse_skill_line(&source).unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn points_duplicate_targeters_at_the_first_one() {
+        let error = parse_skill_line("damage{amount=1} @self @target")
+            .err()
+            .unwrap();
+        assert_eq!(error.code, "targeter_already_defined_error");
+        assert_eq!(error.related_information.len(), 1);
+        let range = error.related_information[0].range;
+        assert_eq!((range.start.character, range.end.character), (17, 22));
+    }
+
+    #[test]
+    fn parses_skill_lines_with_and_without_a_leading_dash() {
+        for source in ["damage{amount=1} @self", "- damage{amount=1} @self"] {
+            let (skill_line, errors) = parse_skill_line(source).unwrap();
+            assert!(errors.is_empty());
+            assert_eq!(skill_line.mechanic().name().value(), "damage");
+            assert!(skill_line.targeter().is_some());
+        }
+    }
+
+    #[test]
+    fn parses_targeters_without_an_mlc() {
+        for name in ["Self", "Target", "Trigger", "origin"] {
+            let source = format!("damage{{amount=1}} @{}", name);
+            let (skill_line, errors) = parse_skill_line(&source).unwrap();
+            assert!(errors.is_empty());
+            let targeter = skill_line.targeter().unwrap();
+            assert_eq!(targeter.name().to_source(), name);
+            assert!(targeter.mlc().is_none());
+        }
+    }
+
+    #[test]
+    fn parses_targeters_with_an_mlc() {
+        let (skill_line, errors) = parse_skill_line("damage{amount=1} @EIR{r=5}").unwrap();
+        assert!(errors.is_empty());
+        let targeter = skill_line.targeter().unwrap();
+        assert_eq!(targeter.name().to_source(), "EIR");
+        assert_eq!(targeter.mlc().unwrap().mlcs()[0].key_name(), "r");
+    }
+
+    #[test]
+    fn reports_unterminated_placeholders_at_the_opening_bracket() {
+        let (skill_line, errors) = parse_skill_line("message{m=<caster.name} @self").unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "unterminated_placeholder_error");
+        assert_eq!(
+            (
+                errors[0].range.start.character,
+                errors[0].range.end.character
+            ),
+            (10, 11)
+        );
+        assert_eq!(skill_line.placeholders().len(), 1);
+        assert!(skill_line.targeter().is_some());
+    }
+
+    #[test]
+    fn parses_placeholders_nested_in_placeholders() {
+        let (skill_line, errors) =
+            parse_skill_line("message{m=<skill.x{a=<caster.level>}>} @self").unwrap();
+        assert!(errors.is_empty());
+        let placeholders = skill_line
+            .placeholders()
+            .iter()
+            .map(|placeholder| placeholder.to_source())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            placeholders,
+            vec!["<skill.x{a=<caster.level>}>", "<caster.level>"]
+        );
+    }
+
+    #[test]
+    fn stops_at_an_unterminated_brace_in_a_name() {
+        let errors = match parse_skill_line("name{unterminated") {
+            Ok((_, errors)) => errors,
+            Err(error) => vec![error],
+        };
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn accepts_each_legal_condition_marker_combination() {
+        for markers in ["", "!", "~", "!~", "~!"] {
+            let source = format!("damage{{amount=1}} @self ?{}health{{h=5}}", markers);
+            let (skill_line, errors) = parse_skill_line(&source).unwrap();
+            assert!(errors.is_empty(), "{}", source);
+            assert_eq!(skill_line.conditions().len(), 1);
+        }
+    }
+
+    #[test]
+    fn reports_repeated_condition_markers() {
+        for markers in ["!!", "~~", "!~!", "~!~"] {
+            let source = format!("damage{{amount=1}} @self ?{}health{{h=5}}", markers);
+            let (_, errors) = parse_skill_line(&source).unwrap();
+            let codes = errors.iter().map(|error| error.code).collect::<Vec<_>>();
+            assert_eq!(
+                codes,
+                vec!["duplicate_condition_marker_error"],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn reports_what_an_unterminated_mlc_expected() {
+        let errors = match parse_skill_line("damage{amount=1") {
+            Ok((_, errors)) => errors,
+            Err(error) => vec![error],
+        };
+        let error = errors
+            .iter()
+            .find(|error| error.code == "unexpected_eof_error")
+            .unwrap();
+        assert!(error.message.contains('}'), "{}", error.message);
+        assert_eq!(error.range.start.character, 15);
+    }
+
+    #[test]
+    fn parses_placeholders_as_targeter_names() {
+        let (skill_line, errors) = parse_skill_line("damage{amount=1} @<skill.mytarget>").unwrap();
+        assert!(errors.is_empty());
+        match skill_line.targeter().unwrap().name() {
+            TargeterName::Placeholder(placeholder) => {
+                assert_eq!(placeholder.to_source(), "<skill.mytarget>")
+            }
+            TargeterName::Identifier(name) => panic!("expected a placeholder, got {:?}", name),
+        }
+    }
+
+    #[test]
+    fn tells_format_tags_from_placeholders() {
+        let (skill_line, errors) =
+            parse_skill_line("message{m=<#FF0000>Hi <caster.name>} @self").unwrap();
+        assert!(errors.is_empty());
+        let value = match skill_line.mlcs()[0].value() {
+            MlcValueContainer::MlcValue(value) => value,
+            MlcValueContainer::InlineSkill(_) => panic!("expected a value"),
+        };
+        let parts = value
+            .identifiers()
+            .iter()
+            .filter_map(|identifier| match identifier {
+                MlcValueIdentifier::FormatTag(tag) => Some(format!("tag {}", tag.to_source())),
+                MlcValueIdentifier::Placeholder(placeholder) => {
+                    Some(format!("placeholder {}", placeholder.to_source()))
+                }
+                MlcValueIdentifier::Identifiers(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(parts, vec!["tag <#FF0000>", "placeholder <caster.name>"]);
+    }
+
+    #[test]
+    fn allows_a_trailing_semicolon_in_mlcs() {
+        let (skill_line, errors) = parse_skill_line("damage{a=1;} @self").unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let keys = skill_line
+            .mlcs()
+            .iter()
+            .map(|mlc| mlc.key_name())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["a"]);
+    }
+
+    #[test]
+    fn keeps_exclamation_marks_outside_conditions_as_text() {
+        let (skill_line, errors) = parse_skill_line("message{m=Hello!} @self").unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        match skill_line.mlcs()[0].value() {
+            MlcValueContainer::MlcValue(value) => assert_eq!(value.to_source(), "Hello!"),
+            MlcValueContainer::InlineSkill(_) => panic!("expected a plain value"),
+        }
+
+        let (skill_line, errors) = parse_skill_line("damage{a=1} @self ~onSignal:hello!").unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            skill_line.trigger().unwrap().arg().unwrap().value(),
+            "hello!"
+        );
+    }
+
+    #[test]
+    fn reads_exclamation_marks_in_conditions_as_negations() {
+        let (skill_line, errors) = parse_skill_line("damage{a=1} @self ?!day").unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let condition = &skill_line.conditions()[0];
+        assert_eq!(condition.name().lexeme.as_deref(), Some("day"));
+        assert_eq!(condition.to_source(), "?!day");
+    }
+
+    #[test]
+    fn reports_unterminated_inline_skills_at_the_opening_bracket() {
+        let (skill_line, errors) = parse_skill_line("skill{s=[ - damage{a=1}} @self").unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "unterminated_inline_skill_error");
+        let range = errors[0].range;
+        assert_eq!((range.start.character, range.end.character), (8, 9));
+        let inline_skill_lines = skill_line.inline_skill_lines();
+        assert_eq!(inline_skill_lines.len(), 1);
+        assert_eq!(inline_skill_lines[0].to_source(), "damage{a=1}");
+        assert!(skill_line.targeter().is_some());
+    }
+}
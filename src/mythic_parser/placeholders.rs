@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+use super::catalog::catalog;
+
+/// A segment of a placeholder's path, e.g. `caster` or `name` in `<caster.name>`,
+/// as listed in the [`catalog`].
+#[derive(Debug, Deserialize)]
+pub struct PlaceholderInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// The segments that can follow this one after a `.`, e.g. `name` for `caster`.
+    #[serde(default)]
+    pub children: Vec<PlaceholderInfo>,
+}
+
+/// Looks up the segments that can follow a placeholder path, ignoring case,
+/// e.g. `name` and `uuid` for `["caster"]`. An empty path gives the namespaces, like `caster`.
+/// Returns `None` if the path isn't known.
+pub fn placeholder_children(path: &[&str]) -> Option<&'static [PlaceholderInfo]> {
+    let mut children = catalog().placeholders.as_slice();
+    for segment in path {
+        children = children
+            .iter()
+            .find(|placeholder| placeholder.name.eq_ignore_ascii_case(segment))?
+            .children
+            .as_slice();
+    }
+    Some(children)
+}
@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+use super::{
+    catalog::{catalog, has_name},
+    mechanics::Attribute,
+};
+
+/// A known MythicMobs targeter, e.g. `@PlayersInRadius{r=10}`, as listed in the [`catalog`].
+#[derive(Debug, Deserialize)]
+pub struct TargeterInfo {
+    /// The targeter's name followed by its aliases.
+    pub names: Vec<String>,
+    pub description: Option<String>,
+    /// Whether the targeter does nothing without options, e.g. `@Location` without coordinates.
+    /// Targeters like `@Origin` and `@Self` are complete on their own.
+    #[serde(default)]
+    pub requires_mlc: bool,
+    #[serde(default)]
+    pub attributes: Vec<Attribute>,
+}
+
+/// Looks up a targeter by name or alias, ignoring case like MythicMobs does.
+pub fn targeter(name: &str) -> Option<&'static TargeterInfo> {
+    catalog()
+        .targeters
+        .iter()
+        .find(|targeter| has_name(&targeter.names, name))
+}
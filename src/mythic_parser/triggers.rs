@@ -0,0 +1,51 @@
+use serde::Deserialize;
+
+use super::{catalog::catalog, mechanics::AttributeType};
+
+/// Whether a trigger takes an argument after a `:`, e.g. `~onTimer:20`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerArgument {
+    Required,
+    Optional,
+    #[default]
+    Forbidden,
+}
+
+/// A known MythicMobs trigger, as listed in the [`catalog`].
+#[derive(Debug, Deserialize)]
+pub struct TriggerInfo {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub argument: TriggerArgument,
+    /// The type of the trigger's argument, e.g. a damage cause for `~onDamaged:FIRE`.
+    /// Arguments of triggers without one aren't checked.
+    #[serde(rename = "argumentType")]
+    pub argument_type: Option<AttributeType>,
+}
+
+impl TriggerInfo {
+    /// Whether an argument is of the type this trigger expects, e.g. `FIRE` is a damage cause.
+    /// Enum values are compared ignoring case.
+    pub fn accepts_argument(&self, arg: &str) -> bool {
+        let arg = arg.trim();
+        match &self.argument_type {
+            Some(AttributeType::Number) => arg.parse::<f64>().is_ok(),
+            Some(AttributeType::Boolean) => arg.parse::<bool>().is_ok(),
+            Some(AttributeType::Enum(values)) => values
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(arg)),
+            Some(AttributeType::String | AttributeType::Variable | AttributeType::Metaskill)
+            | None => true,
+        }
+    }
+}
+
+/// Looks up a trigger by name, ignoring case like MythicMobs does.
+pub fn trigger(name: &str) -> Option<&'static TriggerInfo> {
+    catalog()
+        .triggers
+        .iter()
+        .find(|trigger| trigger.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,472 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag};
+
+use crate::errors::error_registry::{
+    AttributeOutOfRangeError, BareInlineSkillError, DeprecatedMechanicError, DuplicateMlcKeyError,
+    Error, ImpossibleChanceError, InlineSkillTriggerError, InvalidAttributeTypeError,
+    InvalidTriggerArgumentError, MissingTargeterError, MissingTargeterMlcError,
+    TriggerArgumentForbiddenError, TriggerArgumentRequiredError, UnnecessaryChanceError,
+};
+
+use super::{
+    expressions::{
+        Chance, ExprTrait, GenericNameAndMlc, MlcContainer, MlcValue, MlcValueContainer, SkillLine,
+        Targeter, TargeterName, Trigger,
+    },
+    mechanics::{deprecated_mechanic, find_attribute, mechanic, Attribute, AttributeType},
+    targeters::targeter,
+    triggers::{trigger, TriggerArgument},
+};
+
+/// Runs the checks that don't affect whether a skill line parses,
+/// including on the skill lines of any inline skills.
+pub fn validate(skill_line: &SkillLine) -> Vec<Error> {
+    let mut errors = Vec::new();
+    validate_mechanic(skill_line.mechanic(), &mut errors);
+    validate_attribute_types(skill_line.mechanic(), &mut errors);
+    if let Some(targeter) = skill_line.targeter() {
+        validate_targeter_mlc(targeter, &mut errors);
+        validate_targeter_attributes(targeter, &mut errors);
+    }
+    if let Some(trigger) = skill_line.trigger() {
+        validate_trigger(trigger, &mut errors);
+    }
+    if let Some(chance) = skill_line.chance() {
+        validate_chance(chance, &mut errors);
+    }
+    for value in skill_line.bare_inline_skills() {
+        if let Some(range) = value.get_range() {
+            errors.push(BareInlineSkillError::new(range, value.to_source()).to_error());
+        }
+    }
+    let mlcs = skill_line
+        .mechanic()
+        .mlc()
+        .into_iter()
+        .chain(skill_line.targeter().and_then(|targeter| targeter.mlc()))
+        .chain(
+            skill_line
+                .conditions()
+                .iter()
+                .filter_map(|condition| condition.mlc()),
+        );
+    for mlc in mlcs {
+        validate_mlc_keys(mlc, &mut errors);
+    }
+    for inline_skill_line in skill_line.inline_skill_lines() {
+        if let Some(trigger) = inline_skill_line.trigger() {
+            errors.push(
+                InlineSkillTriggerError::new(trigger.get_range())
+                    .to_error()
+                    .with_severity(DiagnosticSeverity::WARNING),
+            );
+        }
+        errors.extend(validate(inline_skill_line));
+    }
+    errors
+}
+
+/// Warns if a skill line's mechanic needs a targeter but doesn't have one.
+/// This isn't part of [`validate`], as skill lines in metaskills and inline skills
+/// inherit the targeter of whatever runs them, so only the caller knows whether it's needed.
+pub fn validate_targeter(skill_line: &SkillLine) -> Vec<Error> {
+    let name = skill_line.mechanic().name();
+    let (info, range) = match (mechanic(&name.value()), name.get_range()) {
+        (Some(info), Some(range)) => (info, range),
+        _ => return Vec::new(),
+    };
+    if !info.requires_targeter || skill_line.targeter().is_some() {
+        return Vec::new();
+    }
+    vec![MissingTargeterError::new(range, name.value())
+        .to_error()
+        .with_severity(DiagnosticSeverity::WARNING)]
+}
+
+/// Hints that a mechanic is deprecated, striking it through in the editor.
+fn validate_mechanic(expr: &GenericNameAndMlc, errors: &mut Vec<Error>) {
+    let (info, range) = match (
+        deprecated_mechanic(&expr.name().value()),
+        expr.name().get_range(),
+    ) {
+        (Some(info), Some(range)) => (info, range),
+        _ => return,
+    };
+    let replacement = info.deprecated.clone().unwrap_or_default();
+    errors.push(
+        DeprecatedMechanicError::new(range, info.names[0].clone(), replacement)
+            .to_error()
+            .with_severity(DiagnosticSeverity::HINT)
+            .with_tags(vec![DiagnosticTag::DEPRECATED]),
+    );
+}
+
+/// Checks that the values of a known mechanic's attributes have the expected types.
+/// Unknown attributes and values with placeholders are skipped.
+fn validate_attribute_types(expr: &GenericNameAndMlc, errors: &mut Vec<Error>) {
+    let (info, mlc) = match (mechanic(&expr.name().value()), expr.mlc()) {
+        (Some(info), Some(mlc)) => (info, mlc),
+        _ => return,
+    };
+    validate_attributes(&info.attributes, mlc, errors);
+}
+
+/// Warns if a known targeter needs options but has none, e.g. `@Location` or `@Location{}`.
+/// Targeters that are complete on their own, like `@Origin`, are fine without them.
+fn validate_targeter_mlc(expr: &Targeter, errors: &mut Vec<Error>) {
+    let name = match expr.name() {
+        TargeterName::Identifier(name) => name,
+        TargeterName::Placeholder(_) => return,
+    };
+    let info = match name.lexeme.as_deref().and_then(targeter) {
+        Some(info) => info,
+        None => return,
+    };
+    if !info.requires_mlc || expr.mlc().is_some_and(|mlc| !mlc.mlcs().is_empty()) {
+        return;
+    }
+    errors.push(
+        MissingTargeterMlcError::new(expr.get_range(), expr.name().to_source())
+            .to_error()
+            .with_severity(DiagnosticSeverity::WARNING),
+    );
+}
+
+/// Like [`validate_attribute_types`], but for a known targeter, e.g. `@PIR{r=-5}`.
+fn validate_targeter_attributes(expr: &Targeter, errors: &mut Vec<Error>) {
+    let (info, mlc) = match (expr.name(), expr.mlc()) {
+        (TargeterName::Identifier(name), Some(mlc)) => {
+            match name.lexeme.as_deref().and_then(targeter) {
+                Some(info) => (info, mlc),
+                None => return,
+            }
+        }
+        _ => return,
+    };
+    validate_attributes(&info.attributes, mlc, errors);
+}
+
+/// Checks the values in an MLC against the given attributes: their types, and for numbers,
+/// their minimum. Unknown attributes and values with placeholders are skipped.
+fn validate_attributes(attributes: &[Attribute], mlc: &MlcContainer, errors: &mut Vec<Error>) {
+    for mlc in mlc.mlcs() {
+        let attribute = find_attribute(attributes, &mlc.key_name());
+        let (attribute, value) = match (attribute, mlc.value()) {
+            (Some(attribute), MlcValueContainer::MlcValue(value)) => (attribute, value),
+            _ => continue,
+        };
+        if value.has_placeholders() {
+            continue;
+        }
+        let range = match value.get_range() {
+            Some(range) => range,
+            None => continue,
+        };
+        if !matches_type(value, &attribute.type_) {
+            errors.push(
+                InvalidAttributeTypeError::new(
+                    range,
+                    mlc.key_name(),
+                    attribute.type_.description(),
+                )
+                .to_error()
+                .with_severity(DiagnosticSeverity::WARNING),
+            );
+            continue;
+        }
+        let minimum = match attribute.minimum {
+            Some(minimum) => minimum,
+            None => continue,
+        };
+        let (min, max) = match (value.as_number(), value.as_number_range()) {
+            (Some(number), _) => (number, number),
+            (None, Some(range)) => range,
+            (None, None) => continue,
+        };
+        if !minimum.allows(min) || !minimum.allows(max) {
+            errors.push(
+                AttributeOutOfRangeError::new(range, mlc.key_name(), minimum.description())
+                    .to_error()
+                    .with_severity(DiagnosticSeverity::WARNING),
+            );
+        }
+    }
+}
+
+fn matches_type(value: &MlcValue, type_: &AttributeType) -> bool {
+    match type_ {
+        AttributeType::Number => value.as_number().is_some() || value.as_number_range().is_some(),
+        AttributeType::Boolean => value.as_bool().is_some(),
+        AttributeType::String | AttributeType::Variable | AttributeType::Metaskill => true,
+        AttributeType::Enum(values) => values
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(value.value().trim())),
+    }
+}
+
+/// Checks that a trigger has an argument only if it takes one, and that it's of the right type.
+fn validate_trigger(expr: &Trigger, errors: &mut Vec<Error>) {
+    let name = expr.name().value();
+    let info = match trigger(&name) {
+        Some(info) => info,
+        None => return,
+    };
+    match (info.argument, expr.arg()) {
+        (TriggerArgument::Required, None) => errors.push(
+            TriggerArgumentRequiredError::new(expr.get_range(), info.name.to_string()).to_error(),
+        ),
+        (TriggerArgument::Forbidden, Some(_)) => errors.push(
+            TriggerArgumentForbiddenError::new(expr.get_range(), info.name.to_string()).to_error(),
+        ),
+        (_, Some(arg)) if !info.accepts_argument(&arg.value()) => {
+            let expected = info
+                .argument_type
+                .as_ref()
+                .map_or_else(String::new, |type_| type_.description());
+            errors.push(
+                InvalidTriggerArgumentError::new(
+                    arg.get_range().unwrap_or(expr.get_range()),
+                    info.name.to_string(),
+                    expected,
+                )
+                .to_error()
+                .with_severity(DiagnosticSeverity::WARNING),
+            )
+        }
+        _ => {}
+    }
+}
+
+/// Warns about keys that are defined more than once in an MLC, as MythicMobs only uses the last one.
+/// Keys are compared case-insensitively.
+fn validate_mlc_keys(expr: &MlcContainer, errors: &mut Vec<Error>) {
+    let mut seen = HashSet::new();
+    for mlc in expr.mlcs() {
+        let key = mlc.key_name();
+        if !seen.insert(key.to_lowercase()) {
+            errors.push(
+                DuplicateMlcKeyError::new(mlc.key().get_range(), key)
+                    .to_error()
+                    .with_severity(DiagnosticSeverity::WARNING),
+            );
+        }
+    }
+}
+
+/// Reports chances below 0% or above 100%, and hints that a chance of exactly 100% is redundant,
+/// fading it out in the editor.
+fn validate_chance(expr: &Chance, errors: &mut Vec<Error>) {
+    if !expr.is_possible() {
+        errors.push(ImpossibleChanceError::new(expr.get_range(), expr.to_source()).to_error());
+        return;
+    }
+    if expr.fraction() != Some(1.0) {
+        return;
+    }
+    errors.push(
+        UnnecessaryChanceError::new(expr.get_range())
+            .to_error()
+            .with_severity(DiagnosticSeverity::HINT)
+            .with_tags(vec![DiagnosticTag::UNNECESSARY]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mythic_parser::parse_skill_line, testing::TestServer};
+
+    use super::*;
+
+    /// Parses and validates a skill line, returning the codes of the errors found.
+    fn error_codes(source: &str) -> Vec<&'static str> {
+        let (skill_line, _) = parse_skill_line(source).unwrap();
+        validate(&skill_line)
+            .iter()
+            .map(|error| error.code)
+            .collect()
+    }
+
+    #[test]
+    fn reports_a_missing_required_trigger_argument() {
+        assert_eq!(
+            error_codes("damage{amount=1} @self ~onTimer"),
+            vec!["trigger_argument_required_error"]
+        );
+    }
+
+    #[test]
+    fn reports_a_forbidden_trigger_argument() {
+        assert_eq!(
+            error_codes("damage{amount=1} @self ~onAttack:5"),
+            vec!["trigger_argument_forbidden_error"]
+        );
+        assert!(error_codes("damage{amount=1} @self ~onTimer:20").is_empty());
+        assert!(error_codes("damage{amount=1} @self ~onUnknown:5").is_empty());
+    }
+
+    #[test]
+    fn warns_about_a_duplicate_mlc_key_once() {
+        let (skill_line, _) = parse_skill_line("damage{amount=1;AMOUNT=2} @self").unwrap();
+        let errors = validate(&skill_line);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "duplicate_mlc_key_error");
+        assert_eq!(errors[0].severity, DiagnosticSeverity::WARNING);
+        assert_eq!(
+            (
+                errors[0].range.start.character,
+                errors[0].range.end.character
+            ),
+            (16, 22)
+        );
+    }
+
+    #[test]
+    fn hints_deprecated_mechanics() {
+        let (skill_line, _) = parse_skill_line("jsonMessage{m=hi} @self").unwrap();
+        let errors = validate(&skill_line);
+        assert_eq!(errors.len(), 1);
+        let diagnostic = errors[0].to_diagnostic(&TestServer::uri("mobs.yml"));
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostic.tags, Some(vec![DiagnosticTag::DEPRECATED]));
+    }
+
+    #[test]
+    fn fades_out_a_chance_of_100_percent() {
+        let (skill_line, _) = parse_skill_line("damage{amount=1} @self 100%").unwrap();
+        let errors = validate(&skill_line);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "unnecessary_chance_error");
+        let diagnostic = errors[0].to_diagnostic(&TestServer::uri("mobs.yml"));
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostic.tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+        assert!(error_codes("damage{amount=1} @self 50%").is_empty());
+    }
+
+    #[test]
+    fn accepts_a_number_where_one_is_expected() {
+        assert!(error_codes("damage{amount=5} @self").is_empty());
+        assert!(error_codes("damage{amount=2.5} @self").is_empty());
+    }
+
+    #[test]
+    fn warns_about_a_string_where_a_number_is_expected() {
+        let (skill_line, _) = parse_skill_line("damage{amount=fire} @self").unwrap();
+        let errors = validate(&skill_line);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "invalid_attribute_type_error");
+        assert_eq!(
+            (
+                errors[0].range.start.character,
+                errors[0].range.end.character
+            ),
+            (14, 18)
+        );
+    }
+
+    #[test]
+    fn warns_about_a_missing_targeter_only_where_one_is_required() {
+        let missing_targeter = |source: &str| {
+            let (skill_line, _) = parse_skill_line(source).unwrap();
+            validate_targeter(&skill_line)
+                .iter()
+                .map(|error| error.code)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            missing_targeter("damage{a=1}"),
+            vec!["missing_targeter_error"]
+        );
+        assert!(missing_targeter("damage{a=1} @self").is_empty());
+        assert!(missing_targeter("message{m=hi}").is_empty());
+    }
+
+    #[test]
+    fn validates_aliases_like_their_mechanic() {
+        assert!(error_codes("d{a=1} @self").is_empty());
+        assert_eq!(
+            error_codes("d{a=fire} @self"),
+            vec!["invalid_attribute_type_error"]
+        );
+    }
+
+    #[test]
+    fn warns_about_targeter_attributes_out_of_range() {
+        assert_eq!(
+            error_codes("damage{amount=1} @PIR{r=-5}"),
+            vec!["attribute_out_of_range_error"]
+        );
+        assert!(error_codes("damage{amount=1} @PIR{r=5}").is_empty());
+    }
+
+    #[test]
+    fn normalizes_chances_to_fractions() {
+        for (source, fraction) in [("0.25", 0.25), ("25%", 0.25), ("1", 1.0), ("150%", 1.5)] {
+            let (skill_line, _) =
+                parse_skill_line(&format!("damage{{amount=1}} @self {}", source)).unwrap();
+            assert_eq!(
+                skill_line.chance().unwrap().fraction(),
+                Some(fraction),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn reports_impossible_chances() {
+        assert!(error_codes("damage{amount=1} @self 0.25").is_empty());
+        assert!(error_codes("damage{amount=1} @self 25%").is_empty());
+        assert_eq!(
+            error_codes("damage{amount=1} @self 1"),
+            vec!["unnecessary_chance_error"]
+        );
+        assert_eq!(
+            error_codes("damage{amount=1} @self 150%"),
+            vec!["impossible_chance_error"]
+        );
+    }
+
+    #[test]
+    fn allows_targeters_without_options_unless_they_need_them() {
+        assert!(error_codes("damage{amount=1} @Origin").is_empty());
+        assert!(error_codes("damage{amount=1} @Trigger").is_empty());
+        assert_eq!(
+            error_codes("damage{amount=1} @Location"),
+            vec!["missing_targeter_mlc_error"]
+        );
+        assert_eq!(
+            error_codes("damage{amount=1} @Location{}"),
+            vec!["missing_targeter_mlc_error"]
+        );
+    }
+
+    #[test]
+    fn matches_attributes_ignoring_case() {
+        assert!(error_codes("damage{Amount=5} @self").is_empty());
+        // the type is only checked if the attribute is found
+        assert_eq!(
+            error_codes("damage{AMOUNT=lots} @self"),
+            vec!["invalid_attribute_type_error"]
+        );
+    }
+
+    #[test]
+    fn warns_about_triggers_in_inline_skills() {
+        assert_eq!(
+            error_codes("skill{s=[ - damage{a=1} ~onAttack ]} @self"),
+            vec!["inline_skill_trigger_error"]
+        );
+        assert!(error_codes("skill{s=[ - damage{a=1} ]} @self ~onAttack").is_empty());
+    }
+
+    #[test]
+    fn validates_trigger_arguments_by_their_type() {
+        assert!(error_codes("damage{amount=1} @self ~onDamaged:FIRE").is_empty());
+        assert!(error_codes("damage{amount=1} @self ~onDamaged:fire").is_empty());
+        assert_eq!(
+            error_codes("damage{amount=1} @self ~onDamaged:NOTACAUSE"),
+            vec!["invalid_trigger_argument_error"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::{
+    jsonrpc::Result,
+    lsp_types::{Diagnostic, DiagnosticOptions, TextDocumentIdentifier, WorkDoneProgressOptions},
+};
+
+use crate::Backend;
+
+/// The request for pull-based diagnostics.
+/// `tower-lsp` doesn't route it yet, so it's handled as a custom method with its own types.
+pub const METHOD: &str = "textDocument/diagnostic";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDiagnosticParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The result id of the last report the client received for this document.
+    pub previous_result_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DocumentDiagnosticReport {
+    #[serde(rename_all = "camelCase")]
+    Full {
+        result_id: String,
+        items: Vec<Diagnostic>,
+    },
+    /// The diagnostics haven't changed since the report with the given result id.
+    #[serde(rename_all = "camelCase")]
+    Unchanged { result_id: String },
+}
+
+/// The capability that tells the client it can pull diagnostics.
+/// Diagnostics are only recomputed when their own document changes or is saved,
+/// so there's no need to pull them again when other documents change.
+pub fn options() -> DiagnosticOptions {
+    DiagnosticOptions {
+        identifier: Some(String::from("mythic")),
+        inter_file_dependencies: false,
+        workspace_diagnostics: false,
+        work_done_progress_options: WorkDoneProgressOptions::default(),
+    }
+}
+
+/// Identifies a set of diagnostics, so unchanged diagnostics don't need to be sent again.
+fn result_id(diagnostics: &[Diagnostic]) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(diagnostics)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+impl Backend {
+    /// Returns the diagnostics computed when the document was last changed.
+    pub async fn document_diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReport> {
+        let items = self
+            .document_map
+            .get(&params.text_document.uri.to_string())
+            .map(|doc_info| doc_info.diagnostics.clone())
+            .unwrap_or_default();
+        let result_id = result_id(&items);
+        if params.previous_result_id.as_ref() == Some(&result_id) {
+            return Ok(DocumentDiagnosticReport::Unchanged { result_id });
+        }
+        Ok(DocumentDiagnosticReport::Full { result_id, items })
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::{DiagnosticSeverity, MessageType};
+
+use crate::{completion::TRIGGER_CHARACTERS, workspace::DEFAULT_MAX_PRELOADED_FILES};
+
+/// Settings sent by the client in `initializationOptions`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// The maximum number of diagnostics published per document, or `None` for no limit.
+    /// When there are more, one more diagnostic is published saying how many were left out.
+    pub max_diagnostics: Option<usize>,
+    /// Whether the client only pulls diagnostics through `textDocument/diagnostic`,
+    /// so the server doesn't need to push them whenever a document changes as well.
+    pub pull_diagnostics: bool,
+    pub completion: CompletionSettings,
+    /// Changes the severity of diagnostics by their code, e.g. `{"deprecated_mechanic_error": "off"}`.
+    pub severity_overrides: HashMap<String, SeverityOverride>,
+    /// The maximum number of workspace files parsed on startup, or `None` for
+    /// [`DEFAULT_MAX_PRELOADED_FILES`]. `0` turns preloading off.
+    pub max_preloaded_files: Option<usize>,
+    /// Whether diagnostic codes are sent as a single string like `"0: syntax_error"`,
+    /// as they were before being split into a number and the source.
+    pub legacy_diagnostic_codes: bool,
+    /// The least severe messages logged to the client, or `None` to read it from `RUST_LOG`.
+    pub log_level: Option<LogLevel>,
+}
+
+/// How much the server logs to the client, from least to most verbose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    #[serde(alias = "warn")]
+    Warning,
+    Info,
+    #[serde(alias = "debug", alias = "trace")]
+    Log,
+}
+
+impl LogLevel {
+    /// Parses a level as written in `RUST_LOG`, e.g. `warn` or `debug`.
+    /// Returns `None` for anything more complex, like per-module filters.
+    pub fn from_env_filter(filter: &str) -> Option<Self> {
+        match filter.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warning),
+            "info" => Some(LogLevel::Info),
+            "debug" | "trace" => Some(LogLevel::Log),
+            _ => None,
+        }
+    }
+    /// Returns the level of a message sent to the client.
+    pub fn of(message_type: MessageType) -> Self {
+        match message_type {
+            MessageType::ERROR => LogLevel::Error,
+            MessageType::WARNING => LogLevel::Warning,
+            MessageType::INFO => LogLevel::Info,
+            _ => LogLevel::Log,
+        }
+    }
+}
+
+/// The codes of diagnostics that are hidden unless their severity is overridden,
+/// as they're only heuristics, e.g. `{"inline_skill_trigger_error": "warning"}`.
+pub const OPT_IN_DIAGNOSTICS: &[&str] = &["inline_skill_trigger_error"];
+
+/// The severity a diagnostic code is overridden to, or `Off` to hide it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityOverride {
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+    Off,
+}
+
+impl SeverityOverride {
+    /// Returns the diagnostic severity, or `None` if diagnostics with this override are hidden.
+    pub fn severity(&self) -> Option<DiagnosticSeverity> {
+        match self {
+            SeverityOverride::Error => Some(DiagnosticSeverity::ERROR),
+            SeverityOverride::Warning => Some(DiagnosticSeverity::WARNING),
+            SeverityOverride::Information => Some(DiagnosticSeverity::INFORMATION),
+            SeverityOverride::Hint => Some(DiagnosticSeverity::HINT),
+            SeverityOverride::Off => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompletionSettings {
+    /// The characters that make the client ask for completions,
+    /// or `None` for [`TRIGGER_CHARACTERS`].
+    pub trigger_characters: Option<Vec<String>>,
+}
+
+impl CompletionSettings {
+    /// Returns the configured trigger characters, falling back to the defaults.
+    pub fn trigger_characters(&self) -> Vec<String> {
+        match &self.trigger_characters {
+            Some(characters) => characters.clone(),
+            None => TRIGGER_CHARACTERS
+                .iter()
+                .map(|character| character.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Settings {
+    /// Returns the configured maximum number of preloaded files, falling back to the default.
+    pub fn max_preloaded_files(&self) -> usize {
+        self.max_preloaded_files
+            .unwrap_or(DEFAULT_MAX_PRELOADED_FILES)
+    }
+    /// Returns the configured log level, falling back to `RUST_LOG` and then to warnings.
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+            .or_else(|| {
+                std::env::var("RUST_LOG")
+                    .ok()
+                    .and_then(|filter| LogLevel::from_env_filter(&filter))
+            })
+            .unwrap_or(LogLevel::Warning)
+    }
+    /// Reads the settings from the client's initialization options.
+    /// Falls back to the defaults if they're missing or invalid.
+    pub fn from_initialization_options(options: Option<&Value>) -> Self {
+        options
+            .and_then(|options| serde_json::from_value(options.clone()).ok())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    CallHierarchyItem, Diagnostic, DiagnosticSeverity, Location, SymbolKind, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::{
+    documents::{SkillDefinition, SkillSymbol},
+    errors::error_registry::UnresolvedSkillReferenceError,
+    utilities::positions_and_ranges::{CustomPosition, CustomRange},
+    Backend,
+};
+
+/// A call from one metaskill to another, e.g. through `skill{s=Other}`.
+#[derive(Debug, Clone)]
+pub struct SkillCall {
+    /// The metaskill the call is made from.
+    pub caller: SkillDefinition,
+    pub caller_uri: Url,
+    /// The reference to the called metaskill.
+    pub callee: SkillSymbol,
+}
+
+impl SkillDefinition {
+    pub fn to_call_hierarchy_item(&self, uri: Url) -> CallHierarchyItem {
+        CallHierarchyItem {
+            name: self.name.clone(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri,
+            range: self.body.to_range(),
+            selection_range: self.range.to_range(),
+            data: None,
+        }
+    }
+}
+
+/// Queries over the metaskill definitions and references of every cached document.
+impl Backend {
+    /// Finds every reference to a metaskill across all cached documents.
+    pub fn find_skill_references(&self, name: &str) -> Vec<Location> {
+        self.document_map
+            .iter()
+            .filter_map(|entry| Some((Url::parse(entry.key()).ok()?, entry)))
+            .flat_map(|(uri, entry)| {
+                entry
+                    .skill_references
+                    .iter()
+                    .filter(|reference| reference.name == name)
+                    .map(|reference| Location::new(uri.clone(), reference.range.to_range()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+    /// Finds where a metaskill is defined across all cached documents.
+    pub fn find_skill_definition(&self, name: &str) -> Option<(Url, SkillDefinition)> {
+        self.document_map.iter().find_map(|entry| {
+            let definition = entry
+                .skill_definitions
+                .iter()
+                .find(|definition| definition.name == name)?;
+            Some((Url::parse(entry.key()).ok()?, definition.clone()))
+        })
+    }
+    /// Finds the name of the metaskill defined or referenced at a position in a document.
+    pub fn skill_name_at(&self, uri: &Url, position: &CustomPosition) -> Option<String> {
+        self.skill_symbol_at(uri, position).map(|(name, _)| name)
+    }
+    /// Like [`Self::skill_name_at`], but also returns the range of the name at the position.
+    pub fn skill_symbol_at(
+        &self,
+        uri: &Url,
+        position: &CustomPosition,
+    ) -> Option<(String, CustomRange)> {
+        let doc_info = self.document_map.get(&uri.to_string())?;
+        let touches =
+            |start: &CustomPosition, end: &CustomPosition| start <= position && position <= end;
+        doc_info
+            .skill_definitions
+            .iter()
+            .find(|definition| touches(&definition.range.start, &definition.range.end))
+            .map(|definition| (definition.name.clone(), definition.range))
+            .or_else(|| {
+                doc_info
+                    .skill_references
+                    .iter()
+                    .find(|reference| touches(&reference.range.start, &reference.range.end))
+                    .map(|reference| (reference.name.clone(), reference.range))
+            })
+    }
+    /// Returns a link to where a metaskill is defined, e.g. `file:///skills.yml#L3,1`.
+    /// The fragment is the 1-based line and column of the definition's name, which VSCode opens at.
+    pub fn skill_definition_link(&self, name: &str) -> Option<Url> {
+        let (mut uri, definition) = self.find_skill_definition(name)?;
+        let start = definition.range.start;
+        uri.set_fragment(Some(&format!(
+            "L{},{}",
+            start.line + 1,
+            start.character + 1
+        )));
+        Some(uri)
+    }
+    /// Warns about the metaskill references in a document that aren't defined in any cached document.
+    /// This searches every document, so it's only done on save rather than on every change.
+    pub fn unresolved_skill_references(&self, uri: &Url) -> Vec<Diagnostic> {
+        let references = match self.document_map.get(&uri.to_string()) {
+            Some(doc_info) => doc_info.skill_references.clone(),
+            None => return Vec::new(),
+        };
+        references
+            .into_iter()
+            .filter(|reference| self.find_skill_definition(&reference.name).is_none())
+            .map(|reference| {
+                UnresolvedSkillReferenceError::new(reference.range, reference.name)
+                    .to_error()
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .to_diagnostic(uri)
+            })
+            .collect()
+    }
+    /// Renames a metaskill's definition and every reference to it across all cached documents.
+    pub fn rename_skill(&self, name: &str, new_name: &str) -> WorkspaceEdit {
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for entry in self.document_map.iter() {
+            let uri = match Url::parse(entry.key()) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            let ranges = entry
+                .skill_definitions
+                .iter()
+                .filter(|definition| definition.name == name)
+                .map(|definition| definition.range)
+                .chain(
+                    entry
+                        .skill_references
+                        .iter()
+                        .filter(|reference| reference.name == name)
+                        .map(|reference| reference.range),
+                );
+            let edits = ranges
+                .map(|range| TextEdit::new(range.to_range(), new_name.to_string()))
+                .collect::<Vec<_>>();
+            if !edits.is_empty() {
+                changes.insert(uri, edits);
+            }
+        }
+        WorkspaceEdit::new(changes)
+    }
+    /// Finds every call between metaskills across all cached documents.
+    /// References outside of a metaskill definition (e.g. in a mob) aren't calls.
+    pub fn find_skill_calls(&self) -> Vec<SkillCall> {
+        self.document_map
+            .iter()
+            .filter_map(|entry| Some((Url::parse(entry.key()).ok()?, entry)))
+            .flat_map(|(uri, entry)| {
+                entry
+                    .skill_references
+                    .iter()
+                    .filter_map(|reference| {
+                        let caller = entry.skill_definitions.iter().find(|definition| {
+                            definition.body.start <= reference.range.start
+                                && reference.range.start < definition.body.end
+                        })?;
+                        Some(SkillCall {
+                            caller: caller.clone(),
+                            caller_uri: uri.clone(),
+                            callee: reference.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
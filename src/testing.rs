@@ -0,0 +1,140 @@
+use std::future::poll_fn;
+
+use futures::{
+    future::{select, Either},
+    pin_mut, FutureExt, SinkExt, StreamExt,
+};
+use serde_json::{json, Value};
+use tower_lsp::{
+    jsonrpc::{Request, Response},
+    lsp_types::{
+        Diagnostic, DidChangeTextDocumentParams, DidOpenTextDocumentParams, InitializeResult,
+        InitializedParams, PublishDiagnosticsParams, TextDocumentContentChangeEvent,
+        TextDocumentItem, Url, VersionedTextDocumentIdentifier,
+    },
+    ClientSocket, LanguageServer, LspService,
+};
+use tower_service::Service;
+
+use crate::{documents::DocumentInfo, Backend};
+
+/// Runs the server against in-memory documents, standing in for the client.
+/// Handlers can be called directly through [`Self::backend`], and whatever the server
+/// sends to the client, like published diagnostics, can be read back.
+pub struct TestServer {
+    service: LspService<Backend>,
+    /// The client's end of the connection, which receives the server's notifications.
+    socket: ClientSocket,
+    messages: Vec<Request>,
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestServer {
+    /// Creates a server that hasn't been initialized yet.
+    /// Like with a real client, it doesn't send anything until [`Self::initialize`] is called.
+    pub fn new() -> Self {
+        let (service, socket) = LspService::build(Backend::new).finish();
+        Self {
+            service,
+            socket,
+            messages: Vec::new(),
+        }
+    }
+    pub fn backend(&self) -> &Backend {
+        self.service.inner()
+    }
+    /// Returns the URI of an in-memory document, e.g. `file:///mobs.yml` for `mobs.yml`.
+    pub fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///{}", name)).unwrap()
+    }
+    /// Initializes the server the way a client would, with the given initialization options.
+    pub async fn initialize(&mut self, options: Value) -> InitializeResult {
+        self.initialize_with(json!({ "capabilities": {}, "initializationOptions": options }))
+            .await
+    }
+    /// Initializes the server with the given `initialize` parameters, e.g. with `workspaceFolders`.
+    pub async fn initialize_with(&mut self, params: Value) -> InitializeResult {
+        let request = Request::build("initialize").params(params).id(0).finish();
+        poll_fn(|cx| self.service.poll_ready(cx)).await.unwrap();
+        let response = self.service.call(request).await.unwrap().unwrap();
+        serde_json::from_value(response.result().unwrap().clone()).unwrap()
+    }
+    /// Tells the server the client is initialized, answering the requests the server sends
+    /// meanwhile, like registering capabilities, with an empty result.
+    pub async fn initialized(&mut self) {
+        let initialized = self.service.inner().initialized(InitializedParams {});
+        pin_mut!(initialized);
+        loop {
+            match select(initialized.as_mut(), self.socket.next()).await {
+                Either::Left(_) | Either::Right((None, _)) => break,
+                Either::Right((Some(message), _)) => match message.id() {
+                    Some(id) => {
+                        let response = Response::from_ok(id.clone(), Value::Null);
+                        self.socket.send(response).await.unwrap();
+                    }
+                    None => self.messages.push(message),
+                },
+            }
+        }
+    }
+    /// Opens a document with the given contents, returning its URI.
+    pub async fn open(&self, name: &str, text: &str) -> Url {
+        let uri = Self::uri(name);
+        self.backend()
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    String::from("yaml"),
+                    0,
+                    text.to_string(),
+                ),
+            })
+            .await;
+        uri
+    }
+    /// Replaces the whole contents of an open document, like a client with full sync does.
+    pub async fn change(&self, uri: &Url, version: i32, text: &str) {
+        self.backend()
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: text.to_string(),
+                }],
+            })
+            .await;
+    }
+    /// Returns the cached information of a document, panicking if it isn't known.
+    pub fn document(&self, uri: &Url) -> DocumentInfo {
+        self.backend()
+            .document_map
+            .get(&uri.to_string())
+            .expect("the document should be known")
+            .clone()
+    }
+    /// Returns every notification the server sent to the client so far.
+    pub fn messages(&mut self) -> &[Request] {
+        while let Some(Some(message)) = self.socket.next().now_or_never() {
+            self.messages.push(message);
+        }
+        &self.messages
+    }
+    /// Returns the diagnostics the server last published for a document,
+    /// or `None` if it never published any.
+    pub fn published_diagnostics(&mut self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        self.messages().iter().rev().find_map(|message| {
+            if message.method() != "textDocument/publishDiagnostics" {
+                return None;
+            }
+            let params = message.params()?.clone();
+            let params = serde_json::from_value::<PublishDiagnosticsParams>(params).ok()?;
+            (params.uri == *uri).then_some(params.diagnostics)
+        })
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::{Debug, Display};
 use marked_yaml::{Marker, Span};
 use tower_lsp::lsp_types::{Position, Range};
 
-#[derive(PartialEq, Eq, PartialOrd, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 pub struct CustomPosition {
     pub line: u32,
     pub character: u32,
@@ -35,12 +35,13 @@ impl CustomPosition {
         let mut line = 0;
         let mut character = 0;
         for (i, current_line) in lines.iter().enumerate() {
-            if offset < current_line.len() as u32 {
+            // an offset equal to the line's length is the end of that line
+            if offset <= current_line.len() as u32 {
                 line = i as u32;
                 character = offset;
                 break;
             }
-            offset -= current_line.len() as u32 + 1;
+            offset = offset.saturating_sub(current_line.len() as u32 + 1);
             line = i as u32 + 1;
             character = offset;
         }
@@ -48,7 +49,7 @@ impl CustomPosition {
     }
     /// Converts a position to a zero-based offset given a source string.
     /// This is the inverse of [`Self::from_offset`]
-    pub fn to_offset(&self, source: &str) -> u32 {
+    pub fn to_offset(self, source: &str) -> u32 {
         let lines = source.lines().collect::<Vec<&str>>();
         let mut offset = 0;
         for (i, current_line) in lines.iter().enumerate() {
@@ -84,7 +85,11 @@ impl CustomPosition {
         self.character -= character;
         self
     }
-    pub fn to_position(&self) -> Position {
+    /// Converts an LSP position, e.g. a cursor from a request. This is the inverse of [`Self::to_position`].
+    pub fn from_position(position: &Position) -> Self {
+        Self::new(position.line, position.character)
+    }
+    pub fn to_position(self) -> Position {
         Position {
             line: self.line,
             character: self.character,
@@ -113,7 +118,31 @@ impl CustomPosition {
         }
     }
     pub fn create_range_with_offset(&self, offset: u32, source: &str) -> CustomRange {
-        CustomRange::new(self.clone(), self.add_offset(offset, source))
+        CustomRange::new(*self, self.add_offset(offset, source))
+    }
+    /// Translates a position inside an embedded source (e.g. a YAML scalar) into
+    /// the enclosing document, given where the embedded source starts.
+    pub fn relative_to(&self, origin: &Self) -> Self {
+        Self {
+            line: self.line + origin.line,
+            character: if self.line == 0 {
+                self.character + origin.character
+            } else {
+                self.character
+            },
+        }
+    }
+    /// Translates a position in the enclosing document into an embedded source that starts at `origin`.
+    /// This is the inverse of [`Self::relative_to`].
+    pub fn relative_from(&self, origin: &Self) -> Self {
+        Self {
+            line: self.line.saturating_sub(origin.line),
+            character: if self.line == origin.line {
+                self.character.saturating_sub(origin.character)
+            } else {
+                self.character
+            },
+        }
     }
 }
 
@@ -133,12 +162,16 @@ impl CustomRange {
     pub fn new(start: CustomPosition, end: CustomPosition) -> Self {
         Self { start, end }
     }
-    pub fn length(&self, source: &str) -> u32 {
-        // if start is more than end, return 1
-        if self.start.compare(&self.end) == std::cmp::Ordering::Greater {
-            return 1;
-        }
-        self.end.to_offset(source) - self.start.to_offset(source)
+    /// Returns the number of bytes in this range of the source, which may span multiple lines.
+    /// Returns `None` if the range is inverted, i.e. it ends before it starts.
+    pub fn length(&self, source: &str) -> Option<u32> {
+        self.end
+            .to_offset(source)
+            .checked_sub(self.start.to_offset(source))
+    }
+    /// Like [`Self::length`], but returns 0 for inverted ranges.
+    pub fn length_saturating(&self, source: &str) -> u32 {
+        self.length(source).unwrap_or(0)
     }
     pub fn from_range(range: &core::ops::Range<usize>, source: &str) -> Self {
         Self {
@@ -146,26 +179,21 @@ impl CustomRange {
             end: CustomPosition::from_offset(range.end as u32, source),
         }
     }
+    /// Creates a range from a `marked_yaml` span, whose markers are 1-based.
+    /// If either end is missing, the range is empty at the other one, or at the very start of
+    /// the document if both are, so it's never inverted.
     pub fn from_span(span: Span) -> Self {
-        Self {
-            // TODO: Fix this unwrap
-            start: CustomPosition::from_marker(
-                &span
-                    .start()
-                    .map(|marker| {
-                        Marker::new(marker.source(), marker.line() - 1, marker.column() - 1)
-                    })
-                    .unwrap_or(Marker::new(0, 0, 1)),
-            ),
-            end: CustomPosition::from_marker(
-                &span
-                    .end()
-                    .map(|marker| {
-                        Marker::new(marker.source(), marker.line() - 1, marker.column() - 1)
-                    })
-                    .unwrap_or(Marker::new(0, 0, 0)),
-            ),
-        }
+        let position = |marker: &Marker| {
+            CustomPosition::new(
+                (marker.line() as u32).saturating_sub(1),
+                (marker.column() as u32).saturating_sub(1),
+            )
+        };
+        let start = span.start().map(position);
+        let end = span.end().map(position);
+        let start = start.or(end).unwrap_or(CustomPosition::new(0, 0));
+        let end = end.unwrap_or(start).max(start);
+        Self { start, end }
     }
     pub fn get_from(&self, source: &str) -> String {
         let start = self.start.to_offset(source);
@@ -204,14 +232,75 @@ impl CustomRange {
             end: self.end.add_offset(offset, source),
         }
     }
+    /// Translates a range inside an embedded source into the enclosing document.
+    /// See [`CustomPosition::relative_to`].
+    pub fn relative_to(&self, origin: &CustomPosition) -> Self {
+        Self {
+            start: self.start.relative_to(origin),
+            end: self.end.relative_to(origin),
+        }
+    }
     pub fn contains(&self, position: &CustomPosition) -> bool {
         self.start.compare(position) == std::cmp::Ordering::Less
             && self.end.compare(position) == std::cmp::Ordering::Greater
     }
-    pub fn to_range(&self) -> Range {
+    pub fn to_range(self) -> Range {
         Range {
             start: self.start.to_position(),
             end: self.end.to_position(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_positions_both_ways() {
+        let position = CustomPosition::new(3, 7);
+        assert_eq!(
+            CustomPosition::from_position(&position.to_position()),
+            position
+        );
+        let position = Position::new(3, 7);
+        assert_eq!(
+            CustomPosition::from_position(&position).to_position(),
+            position
+        );
+    }
+
+    #[test]
+    fn creates_empty_ranges_from_spans_missing_an_end() {
+        let ends = |span| {
+            let range = CustomRange::from_span(span);
+            (range.start, range.end)
+        };
+        let empty_at = |line, character| {
+            let position = CustomPosition::new(line, character);
+            (position, position)
+        };
+        let mut without_start = Span::new_blank();
+        without_start.set_end(Some(Marker::new(0, 3, 5)));
+        assert_eq!(ends(without_start), empty_at(2, 4));
+        assert_eq!(ends(Span::new_start(Marker::new(0, 3, 5))), empty_at(2, 4));
+        assert_eq!(ends(Span::new_blank()), empty_at(0, 0));
+    }
+
+    #[test]
+    fn measures_the_length_of_ranges() {
+        let source = "ab\ncde\nf";
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            CustomRange::new(
+                CustomPosition::new(start.0, start.1),
+                CustomPosition::new(end.0, end.1),
+            )
+        };
+        assert_eq!(range((1, 0), (1, 3)).length(source), Some(3));
+        // the line breaks in between count too
+        assert_eq!(range((0, 1), (2, 1)).length(source), Some(7));
+        assert_eq!(range((1, 1), (1, 1)).length(source), Some(0));
+        assert_eq!(range((2, 0), (0, 1)).length(source), None);
+        assert_eq!(range((2, 0), (0, 1)).length_saturating(source), 0);
+    }
+}
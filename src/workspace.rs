@@ -0,0 +1,135 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tower_lsp::lsp_types::{MessageType, Url};
+
+use crate::Backend;
+
+/// The maximum number of files preloaded from the workspace, unless configured otherwise.
+pub const DEFAULT_MAX_PRELOADED_FILES: usize = 1000;
+
+/// Finds up to `max` YAML files under a directory, skipping hidden directories like `.git`.
+/// Returns whether there were more files than that, along with the files.
+pub fn find_yaml_files(root: &Path, max: usize) -> (Vec<PathBuf>, bool) {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if file_type.is_dir() && !hidden {
+                directories.push(path);
+            } else if file_type.is_file() && is_yaml(&path) {
+                if files.len() == max {
+                    return (files, true);
+                }
+                files.push(path);
+            }
+        }
+    }
+    (files, false)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "yml" || extension == "yaml")
+}
+
+impl Backend {
+    /// Parses every YAML file in the workspace that isn't cached yet, so that metaskills
+    /// defined in files that were never opened can still be found.
+    pub async fn preload_workspace(&self, roots: &[PathBuf]) {
+        let max = self.settings.read().unwrap().max_preloaded_files();
+        if max == 0 {
+            return;
+        }
+        let mut remaining = max;
+        for root in roots {
+            let (files, truncated) = find_yaml_files(root, remaining);
+            remaining -= files.len();
+            for path in files {
+                let uri = match Url::from_file_path(&path) {
+                    Ok(uri) => uri,
+                    Err(_) => continue,
+                };
+                if self.document_map.contains_key(&uri.to_string()) {
+                    continue;
+                }
+                match tokio::fs::read_to_string(&path).await {
+                    Ok(text) => {
+                        self.update_document(&uri, text);
+                    }
+                    Err(error) => {
+                        self.log_async(
+                            MessageType::WARNING,
+                            format!("Failed to read {}: {}", path.display(), error),
+                        )
+                        .await
+                    }
+                }
+            }
+            if truncated {
+                self.log_async(
+                    MessageType::WARNING,
+                    format!("Only the first {} files of the workspace were loaded.", max),
+                )
+                .await;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::testing::TestServer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn preloaded_files_resolve_references() {
+        let root = std::env::temp_dir().join(format!("mythic-preload-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("skills")).unwrap();
+        let skills = "Boom:\n  Skills:\n  - damage{amount=1}\n";
+        fs::write(root.join("skills").join("boom.yml"), skills).unwrap();
+        fs::write(root.join("notes.txt"), "Other:\n  Skills: []\n").unwrap();
+
+        let mut server = TestServer::new();
+        let folder = Url::from_file_path(&root).unwrap();
+        server
+            .initialize_with(json!({
+                "capabilities": {},
+                "workspaceFolders": [{ "uri": folder, "name": "pack" }],
+            }))
+            .await;
+        let uri = server
+            .open(
+                "mobs.yml",
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - skill{s=Boom}\n",
+            )
+            .await;
+        // read what was sent so far, so the server doesn't wait for the client to catch up
+        server.messages();
+        assert_eq!(server.backend().unresolved_skill_references(&uri).len(), 1);
+        server.initialized().await;
+        assert!(server
+            .backend()
+            .unresolved_skill_references(&uri)
+            .is_empty());
+        assert_eq!(server.backend().document_map.len(), 2);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
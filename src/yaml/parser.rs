@@ -1,33 +1,35 @@
-use std::any::type_name;
+use std::{collections::HashMap, sync::Arc};
 
 use marked_yaml::{
+    types::MarkedScalarNode,
     LoadError::{
         MappingKeyMustBeScalar, ScanError, TopLevelMustBeMapping, UnexpectedAnchor, UnexpectedTag,
     },
-    Node, Span,
+    Node,
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{DiagnosticSeverity, MessageType, SemanticTokenType};
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker as EventMarker, Scanner, TScalarStyle, Token, TokenType as ScannerTokenType},
 };
-use tower_lsp::lsp_types::{MessageType, SemanticToken, SemanticTokenType};
 
 use crate::{
-    documents::{get_index_for_type, DocumentInfo, ImCompleteSemanticToken},
-    errors::error_registry::SyntaxError,
+    documents::{
+        get_index_for_type, DocumentInfo, ImCompleteSemanticToken, SkillDefinition, SkillScalar,
+        SkillSymbol,
+    },
+    errors::error_registry::{DuplicateKeyError, Error, RelatedInformation, SyntaxError},
+    mythic_parser::{
+        highlighting::highlight,
+        parse_skill_line,
+        validation::{validate, validate_targeter},
+    },
     utilities::positions_and_ranges::{CustomPosition, CustomRange},
+    yaml::schemas::schema::{YamlSchema, YamlSchemaString},
     Backend,
 };
 
-pub fn get_start_and_length_from_span(node: &Node, source: &str) -> (usize, usize) {
-    let span = node.span();
-    let start = span
-        .start()
-        .map(|x| CustomPosition::from_marker(x).subtract_line(1).to_offset(source))
-        .unwrap_or(0) as usize;
-    let end = span
-        .end()
-        .map(|x| CustomPosition::from_marker(x).subtract_line(1).to_offset(source))
-        .unwrap_or(0) as usize;
-    (start - 1, if end > start { end - start } else { 1 })
-}
-
 pub fn node_length(node: &Node) -> usize {
     match node {
         Node::Scalar(scalar) => scalar.len(),
@@ -44,7 +46,180 @@ pub fn node_type(node: &Node) -> String {
     }
 }
 
-pub fn visit(backend: &Backend, doc: &mut DocumentInfo, node: Node) {
+/// Gets the zero-based position of the first character of a scalar's value.
+/// For quoted scalars, this is the character after the opening quote.
+/// This works the same for block and flow style, e.g. `{a: 1, 'b': 2}`.
+pub fn scalar_start(node: &MarkedScalarNode, source: &Rope) -> CustomPosition {
+    let mut position = node
+        .span()
+        .start()
+        .map(|marker| {
+            CustomPosition::new(
+                (marker.line() as u32).saturating_sub(1),
+                (marker.column() as u32).saturating_sub(1),
+            )
+        })
+        .unwrap_or(CustomPosition::new(0, 0));
+    if source
+        .get_char(char_offset(&position, source))
+        .is_some_and(|c| c == '"' || c == '\'')
+    {
+        position.add_character(1);
+    }
+    position
+}
+
+/// Gets the zero-based character offset of a position, without walking the whole source.
+fn char_offset(position: &CustomPosition, source: &Rope) -> usize {
+    let line_start = source
+        .try_line_to_char(position.line as usize)
+        .unwrap_or(source.len_chars());
+    (line_start + position.character as usize).min(source.len_chars())
+}
+
+/// Gets the byte offset of the first character of a scalar's value, as semantic tokens expect.
+fn scalar_offset(node: &MarkedScalarNode, source: &Rope) -> usize {
+    source.char_to_byte(char_offset(&scalar_start(node, source), source))
+}
+
+/// Gets the zero-based position where a node starts. Collections start at their first key or item,
+/// as that's where the cursor is when it's on them.
+fn node_start(node: &Node, source: &Rope) -> Option<CustomPosition> {
+    match node {
+        Node::Scalar(scalar) => Some(scalar_start(scalar, source)),
+        Node::Mapping(mapping) => mapping
+            .iter()
+            .next()
+            .map(|(key, _)| scalar_start(key, source)),
+        Node::Sequence(sequence) => sequence.first().and_then(|item| node_start(item, source)),
+    }
+}
+
+/// Gets the keys and list indices leading to the node at a position, e.g. `MyMob`, `Skills`, `[0]`.
+/// Each entry of a collection spans until the next one starts, so comments and blank lines
+/// belong to the entry above them.
+pub fn node_path(node: &Node, position: &CustomPosition, source: &Rope) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut node = node;
+    loop {
+        node = match node {
+            Node::Scalar(_) => break,
+            Node::Mapping(mapping) => {
+                let entry = mapping
+                    .iter()
+                    .rev()
+                    .find(|(key, _)| scalar_start(key, source) <= *position);
+                match entry {
+                    Some((key, value)) => {
+                        path.push(key.to_string());
+                        value
+                    }
+                    None => break,
+                }
+            }
+            Node::Sequence(sequence) => {
+                let item = sequence.iter().enumerate().rev().find(|(_, item)| {
+                    node_start(item, source).is_some_and(|start| start <= *position)
+                });
+                match item {
+                    Some((index, item)) => {
+                        path.push(format!("[{}]", index));
+                        item
+                    }
+                    None => break,
+                }
+            }
+        };
+    }
+    path
+}
+
+/// Parses a scalar as a skill line, adding its semantic tokens, diagnostics,
+/// and metaskill references to the document.
+/// If the scalar wasn't touched by the latest edit, the previous results are reused instead.
+/// Returns `false` if the scalar isn't a valid skill line, after reporting why.
+pub fn visit_skill_line(doc: &mut DocumentInfo, node: &MarkedScalarNode) -> bool {
+    let start = scalar_start(node, &doc.source);
+    let offset = scalar_offset(node, &doc.source);
+    let reusable = doc
+        .reusable_skill_scalars
+        .remove(&offset)
+        .filter(|scalar| scalar.source == node.as_str());
+    let scalar = match reusable {
+        Some(scalar) => scalar,
+        None => {
+            let (skill_line, mut errors) = match parse_skill_line(node.as_str()) {
+                Ok(result) => result,
+                Err(error) => {
+                    let diagnostic = error.relative_to(&start).to_diagnostic(&doc.uri);
+                    doc.push_diagnostic_dedup(diagnostic);
+                    return false;
+                }
+            };
+            errors.extend(validate(&skill_line));
+            SkillScalar {
+                offset,
+                source: node.to_string(),
+                semantic_tokens: highlight(&skill_line),
+                errors,
+                skill_references: skill_line
+                    .skill_references()
+                    .into_iter()
+                    .map(|(name, range)| SkillSymbol::new(name, range))
+                    .collect(),
+                skill_line: Arc::new(skill_line),
+            }
+        }
+    };
+    scalar.apply(doc, &start);
+    // skill lines in metaskills inherit their targeter from whatever calls them
+    let in_skill_definition = doc
+        .skill_definitions
+        .iter()
+        .any(|definition| definition.body.start <= start && start < definition.body.end);
+    if !in_skill_definition {
+        for error in validate_targeter(&scalar.skill_line) {
+            let diagnostic = error.relative_to(&start).to_diagnostic(&doc.uri);
+            doc.push_diagnostic_dedup(diagnostic);
+        }
+    }
+    doc.skill_scalars.push(scalar);
+    true
+}
+
+/// Records every metaskill defined at the top level of a document.
+/// A metaskill is any top-level entry with a `Skills` list that isn't a mob, i.e. has no `Type`.
+/// Entries with a merge key are assumed to be mobs, as their `Type` may come from the merged mapping.
+pub fn collect_skill_definitions(doc: &mut DocumentInfo, node: &Node, source: &str) {
+    let mapping = match node.as_mapping() {
+        Some(mapping) => mapping,
+        None => return,
+    };
+    let end = CustomPosition::from_offset(source.len() as u32, source);
+    let entries = mapping
+        .iter()
+        .map(|(key, value)| (key, value, scalar_start(key, &doc.source)))
+        .collect::<Vec<_>>();
+    for (i, (key, value, start)) in entries.iter().enumerate() {
+        let is_skill = value.as_mapping().is_some_and(|value| {
+            value.get_sequence("Skills").is_some()
+                && value.get_node("Type").is_none()
+                && value.get_node(MERGE_KEY).is_none()
+        });
+        if !is_skill {
+            continue;
+        }
+        // each definition's body ends where the next top-level key starts
+        let body_end = entries.get(i + 1).map_or(end, |(_, _, next)| *next);
+        doc.skill_definitions.push(SkillDefinition::new(
+            key.to_string(),
+            start.create_range_with_offset(key.len() as u32, source),
+            CustomRange::new(*start, body_end),
+        ));
+    }
+}
+
+pub fn visit(backend: &Backend, doc: &mut DocumentInfo, node: Node, source: &str) {
     // visiting node {} with span {}...{}
     backend.log(
         MessageType::INFO,
@@ -58,11 +233,19 @@ pub fn visit(backend: &Backend, doc: &mut DocumentInfo, node: Node) {
     );
     match node {
         // string
-        Node::Scalar(node) => doc.semantic_tokens.push(ImCompleteSemanticToken {
-            start: get_start_and_length_from_span(&Node::Scalar(node.clone()), &doc.source.to_string()).0,
-            token_type: get_index_for_type(SemanticTokenType::STRING),
-            length: node.len(),
-        }),
+        Node::Scalar(node) => {
+            let start = scalar_offset(&node, &doc.source);
+            // empty values are read as `~`, positioned at whatever comes after them
+            if node.as_str() == "~" && source.get(start..start + 1) != Some("~") {
+                return;
+            }
+            doc.semantic_tokens.push(ImCompleteSemanticToken {
+                start,
+                token_type: get_index_for_type(SemanticTokenType::STRING),
+                length: node.len(),
+                modifiers: 0,
+            })
+        }
         // key-value pair
         Node::Mapping(mut node) => {
             // highlight the keys as properties
@@ -70,26 +253,333 @@ pub fn visit(backend: &Backend, doc: &mut DocumentInfo, node: Node) {
                 let key = entry.key();
                 let value = entry.get();
 
+                // the merge key isn't a property of its own, it pulls in another mapping's
+                let token_type = if key.as_str() == MERGE_KEY {
+                    SemanticTokenType::OPERATOR
+                } else {
+                    SemanticTokenType::PROPERTY
+                };
                 doc.semantic_tokens.push(ImCompleteSemanticToken {
-                    start: get_start_and_length_from_span(&Node::Scalar(key.clone()), &doc.source.to_string()).0,
+                    start: scalar_offset(key, &doc.source),
                     length: key.len(),
-                    token_type: get_index_for_type(SemanticTokenType::PROPERTY),
+                    token_type: get_index_for_type(token_type),
+                    modifiers: 0,
                 });
-                visit(backend, doc, value.clone());
+                match value.as_sequence() {
+                    // skill lines are highlighted by the Mythic parser instead
+                    Some(skills) if key.as_str() == "Skills" => {
+                        skills.iter().for_each(|skill| match skill.as_scalar() {
+                            Some(scalar) if visit_skill_line(doc, scalar) => {}
+                            _ => {
+                                // a skill line is a string, e.g. not a mapping from a stray `:`
+                                YamlSchemaString::new(None).validate(doc, skill);
+                                visit(backend, doc, skill.clone(), source)
+                            }
+                        })
+                    }
+                    _ => visit(backend, doc, value.clone(), source),
+                }
             })
         }
         // array
-        Node::Sequence(mut mode) => {
-            mode.iter().for_each(|node| {
-                visit(backend, doc, node.clone());
-            })
+        Node::Sequence(mode) => mode.iter().for_each(|node| {
+            visit(backend, doc, node.clone(), source);
+        }),
+    }
+}
+
+/// A collection that [`DuplicateKeyFinder`] is inside of.
+enum Collection {
+    Sequence,
+    Mapping {
+        /// Where each key of the mapping was first defined.
+        keys: HashMap<String, CustomRange>,
+        /// Whether the next node is a key rather than a value.
+        expecting_key: bool,
+    },
+}
+
+/// Reads the YAML events of a document to find keys defined more than once in the same mapping.
+struct DuplicateKeyFinder<'a> {
+    source: &'a str,
+    collections: Vec<Collection>,
+    errors: Vec<Error>,
+}
+
+impl DuplicateKeyFinder<'_> {
+    /// Records that a node started, returning whether it's a mapping key.
+    fn start_node(&mut self) -> bool {
+        match self.collections.last_mut() {
+            Some(Collection::Mapping { expecting_key, .. }) => {
+                *expecting_key = !*expecting_key;
+                !*expecting_key
+            }
+            _ => false,
+        }
+    }
+
+    fn key(&mut self, key: String, style: TScalarStyle, marker: EventMarker) {
+        let mut start = CustomPosition::new(
+            (marker.line() as u32).saturating_sub(1),
+            marker.col() as u32,
+        );
+        if !matches!(style, TScalarStyle::Plain) {
+            start.add_character(1);
+        }
+        let range = start.create_range_with_offset(key.len() as u32, self.source);
+        let keys = match self.collections.last_mut() {
+            Some(Collection::Mapping { keys, .. }) => keys,
+            _ => return,
+        };
+        match keys.get(&key) {
+            Some(first) => self.errors.push(
+                DuplicateKeyError::new(range, key)
+                    .to_error()
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .with_related_information(RelatedInformation::new(
+                        *first,
+                        String::from("The key is first defined here."),
+                    )),
+            ),
+            None => {
+                keys.insert(key, range);
+            }
         }
     }
 }
 
-pub fn parse<'a>(backend: &'a Backend, mut doc: &'a mut DocumentInfo) -> &'a DocumentInfo {
+impl MarkedEventReceiver for DuplicateKeyFinder<'_> {
+    fn on_event(&mut self, event: Event, marker: EventMarker) {
+        match event {
+            Event::Scalar(value, style, _, _) => {
+                let is_key = self.start_node();
+                if is_key {
+                    self.key(value, style, marker);
+                }
+            }
+            Event::Alias(_) => {
+                self.start_node();
+            }
+            Event::SequenceStart(_) => {
+                self.start_node();
+                self.collections.push(Collection::Sequence);
+            }
+            Event::MappingStart(_) => {
+                self.start_node();
+                self.collections.push(Collection::Mapping {
+                    keys: HashMap::new(),
+                    expecting_key: true,
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.collections.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds keys that are defined more than once in the same mapping, e.g. two `Skills` in a mob.
+/// MythicMobs only uses the last one, and so does `marked_yaml`, so this reads the events itself.
+/// Each duplicate is a warning that points at where the key is first defined.
+pub fn duplicate_keys(source: &str) -> Vec<Error> {
+    let mut finder = DuplicateKeyFinder {
+        source,
+        collections: Vec::new(),
+        errors: Vec::new(),
+    };
+    // syntax errors are already reported by `marked_yaml`
+    let _ = Parser::new(source.chars()).load(&mut finder, false);
+    finder.errors
+}
+
+/// The key that merges another mapping into this one, e.g. `<<: *base`.
+pub const MERGE_KEY: &str = "<<";
+
+/// Blanks out YAML anchors (`&base`) and aliases (`*base`), which `marked_yaml` rejects,
+/// so that documents using templates can still be parsed. Every other character is kept
+/// where it is, so positions in the result are the same as in the source.
+/// Only real anchors and aliases are blanked, so `a &b` in a skill line or `&7Sword` in
+/// a block scalar is kept. Use [`resolve_aliases`] to get the values of the aliases back.
+pub fn blank_anchors(source: &str) -> String {
+    let mut blanked = source.chars().collect::<Vec<_>>();
+    // the scanner stops at the first syntax error, which `marked_yaml` reports anyway
+    for Token(marker, token) in Scanner::new(source.chars()) {
+        if let ScannerTokenType::Anchor(name) | ScannerTokenType::Alias(name) = token {
+            // the name comes after the `&` or `*`
+            let length = name.chars().count() + 1;
+            blanked
+                .iter_mut()
+                .skip(marker.index())
+                .take(length)
+                .for_each(|c| *c = ' ');
+        }
+    }
+    blanked.into_iter().collect()
+}
+
+/// A step on the way from the root of a document to one of its nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A collection that [`AnchorFinder`] is inside of, along with the path leading to it.
+enum AnchorCollection {
+    Sequence {
+        path: Vec<PathSegment>,
+        /// How many items of the sequence have been read so far.
+        length: usize,
+    },
+    Mapping {
+        path: Vec<PathSegment>,
+        /// The key of the value that comes next, or `None` if a key comes next.
+        key: Option<String>,
+    },
+}
+
+/// Reads the YAML events of a document to find the paths of its anchored nodes and aliases.
+#[derive(Default)]
+struct AnchorFinder {
+    collections: Vec<AnchorCollection>,
+    /// The path of the node each anchor is on, by the anchor's ID.
+    anchors: HashMap<usize, Vec<PathSegment>>,
+    /// The path of each alias and the ID of the anchor it refers to, in document order.
+    aliases: Vec<(Vec<PathSegment>, usize)>,
+}
+
+impl AnchorFinder {
+    /// Records that a node started, returning its path, or `None` if it's a mapping key.
+    fn start_node(&mut self) -> Option<Vec<PathSegment>> {
+        match self.collections.last_mut() {
+            None => Some(Vec::new()),
+            Some(AnchorCollection::Sequence { path, length }) => {
+                let mut path = path.clone();
+                path.push(PathSegment::Index(*length));
+                *length += 1;
+                Some(path)
+            }
+            Some(AnchorCollection::Mapping { path, key }) => {
+                let key = key.take()?;
+                let mut path = path.clone();
+                path.push(PathSegment::Key(key));
+                Some(path)
+            }
+        }
+    }
+
+    /// Records the key of the mapping entry whose value comes next.
+    fn key(&mut self, key: String) {
+        if let Some(AnchorCollection::Mapping { key: next, .. }) = self.collections.last_mut() {
+            *next = Some(key);
+        }
+    }
+
+    /// Records that a collection started, along with its anchor, returning its path.
+    /// Collections can't be looked up as keys in `marked_yaml`, so those get no path.
+    fn start_collection(&mut self, anchor: usize) -> Option<Vec<PathSegment>> {
+        let path = self.start_node();
+        match &path {
+            None => self.key(String::new()),
+            Some(path) if anchor != 0 => {
+                self.anchors.insert(anchor, path.clone());
+            }
+            _ => {}
+        }
+        path
+    }
+}
+
+impl MarkedEventReceiver for AnchorFinder {
+    fn on_event(&mut self, event: Event, _: EventMarker) {
+        match event {
+            Event::Scalar(value, _, anchor, _) => match self.start_node() {
+                None => self.key(value),
+                Some(path) if anchor != 0 => {
+                    self.anchors.insert(anchor, path);
+                }
+                _ => {}
+            },
+            Event::Alias(anchor) => match self.start_node() {
+                None => self.key(String::new()),
+                Some(path) => self.aliases.push((path, anchor)),
+            },
+            Event::SequenceStart(anchor) => {
+                let path = self.start_collection(anchor).unwrap_or_default();
+                self.collections
+                    .push(AnchorCollection::Sequence { path, length: 0 });
+            }
+            Event::MappingStart(anchor) => {
+                let path = self.start_collection(anchor).unwrap_or_default();
+                self.collections
+                    .push(AnchorCollection::Mapping { path, key: None });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.collections.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gets the node at the end of a path, if there is one.
+fn node_at_path<'a>(node: &'a mut Node, path: &[PathSegment]) -> Option<&'a mut Node> {
+    path.iter()
+        .try_fold(node, |node, segment| match (node, segment) {
+            (Node::Mapping(mapping), PathSegment::Key(key)) => mapping.get_mut(key.as_str()),
+            (Node::Sequence(sequence), PathSegment::Index(index)) => sequence.get_mut(*index),
+            _ => None,
+        })
+}
+
+/// Replaces the aliases blanked by [`blank_anchors`] with a copy of the node their anchor is on,
+/// so that e.g. `Skills: *common` is read as the list it refers to instead of an empty value.
+/// The copies keep the positions of the anchored node.
+pub fn resolve_aliases(source: &str, node: &mut Node) {
+    let mut finder = AnchorFinder::default();
+    // syntax errors are already reported by `marked_yaml`
+    let _ = Parser::new(source.chars()).load(&mut finder, false);
+    // in document order, so that aliases inside anchored nodes are resolved before they're copied
+    for (path, anchor) in finder.aliases {
+        let value = finder
+            .anchors
+            .get(&anchor)
+            .and_then(|anchor_path| node_at_path(node, anchor_path))
+            .cloned();
+        if let (Some(value), Some(alias)) = (value, node_at_path(node, &path)) {
+            *alias = value;
+        }
+    }
+}
+
+/// Strips the leading "line:column: " marker from a `marked_yaml` error message.
+/// Falls back to the original message if it isn't in that format.
+pub fn strip_marker_prefix(message: &str) -> &str {
+    let mut rest = message;
+    let mut stripped = false;
+    while let Some((number, tail)) = rest.split_once(':') {
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            break;
+        }
+        rest = tail;
+        stripped = true;
+    }
+    let rest = rest.trim_start();
+    if !stripped || rest.is_empty() {
+        return message;
+    }
+    rest
+}
+
+pub fn parse<'a>(backend: &'a Backend, doc: &'a mut DocumentInfo) -> &'a DocumentInfo {
     let source = &doc.source.to_string();
-    let node = marked_yaml::parse_yaml(0, source);
+    // a new file has nothing to check yet, and `marked_yaml` rejects some blank documents, e.g. tabs
+    if source.trim().is_empty() {
+        return doc;
+    }
+    let blanked = blank_anchors(source);
+    let node = marked_yaml::parse_yaml(0, blanked.as_str());
     if let Err(e) = node {
         // struct is LoadError(Marker)
         let mut range_start = match e {
@@ -100,29 +590,35 @@ pub fn parse<'a>(backend: &'a Backend, mut doc: &'a mut DocumentInfo) -> &'a Doc
             | ScanError(marker, _) => CustomPosition::from_marker(&marker),
         };
         // subtract 1 line because it's 1-indexed
+        // (saturating, as errors at the very start of the stream can already be on line 0)
         // subtract 1 character because it's 1-indexed
-        range_start.set_line(range_start.line - 1);
+        range_start.set_line(range_start.line.saturating_sub(1));
         // range_start.set_character(range_start.character - 1);
 
-        let mut message = e.to_string();
+        let message = e.to_string();
+        let message = strip_marker_prefix(&message).to_string();
 
-        // the message contains a "line:column: " at the start, remove the first 2 colons and the space
-        message.drain(0..message.find(':').unwrap() + 1);
-        message.drain(0..message.find(':').unwrap() + 1);
-        message.drain(0..message.find(' ').unwrap() + 1);
-
-        doc.diagnostics.push(
+        doc.push_diagnostic_dedup(
             SyntaxError::new(
                 CustomRange::new(range_start, range_start.add_offset(1, source)),
                 message,
             )
             .to_error()
-            .to_diagnostic(),
+            .to_diagnostic(&doc.uri),
         );
         return doc;
     }
     let node = node.unwrap();
-    visit(backend, &mut doc, node);
+    let mut resolved = node.clone();
+    resolve_aliases(source, &mut resolved);
+    for error in duplicate_keys(&blanked) {
+        let diagnostic = error.to_diagnostic(&doc.uri);
+        doc.push_diagnostic_dedup(diagnostic);
+    }
+    collect_skill_definitions(doc, &resolved, source);
+    doc.yaml = Some(resolved);
+    // aliases aren't resolved here, as their nodes are already visited where they're anchored
+    visit(backend, doc, node, source);
 
     doc
 }
@@ -153,3 +649,194 @@ pub fn parse<'a>(backend: &'a Backend, mut doc: &'a mut DocumentInfo) -> &'a Doc
 //     // get range
 //     yaml;
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestServer;
+
+    #[test]
+    fn strips_the_marker_prefix() {
+        assert_eq!(
+            strip_marker_prefix("3:5: did not find expected key"),
+            "did not find expected key"
+        );
+    }
+
+    #[test]
+    fn keeps_messages_without_a_marker_prefix() {
+        assert_eq!(
+            strip_marker_prefix("did not find expected key"),
+            "did not find expected key"
+        );
+        assert_eq!(strip_marker_prefix("3:5:"), "3:5:");
+        assert_eq!(strip_marker_prefix("3:5:no space"), "no space");
+        assert_eq!(strip_marker_prefix(""), "");
+    }
+
+    #[test]
+    fn reports_first_line_syntax_errors_on_the_first_line() {
+        let server = TestServer::new();
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), String::from("a: b: c\n"));
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_eq!(doc.diagnostics[0].range.start.line, 0);
+    }
+
+    #[test]
+    fn highlights_the_keys_of_flow_mappings() {
+        let server = TestServer::new();
+        let source = "Mob: {Type: ZOMBIE, Health: 20}\n";
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        let properties = doc
+            .semantic_tokens
+            .iter()
+            .filter(|token| token.token_type == get_index_for_type(SemanticTokenType::PROPERTY))
+            .map(|token| &source[token.start..token.start + token.length])
+            .collect::<Vec<_>>();
+        assert_eq!(properties, vec!["Mob", "Type", "Health"]);
+    }
+
+    #[test]
+    fn highlights_keys_after_multibyte_characters() {
+        let server = TestServer::new();
+        let source = "Mob: {Display: 'Gänseblümchen', Type: ZOMBIE}\n";
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        let properties = doc
+            .semantic_tokens
+            .iter()
+            .filter(|token| token.token_type == get_index_for_type(SemanticTokenType::PROPERTY))
+            .map(|token| &source[token.start..token.start + token.length])
+            .collect::<Vec<_>>();
+        assert_eq!(properties, vec!["Mob", "Display", "Type"]);
+    }
+
+    #[test]
+    fn reports_malformed_skill_lines_at_their_offset() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from(
+                "Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage{amount=1} @target\n  \
+                 - damage{amount=1 @target\n",
+            ),
+        );
+        assert_eq!(doc.diagnostics.len(), 1);
+        // the MLC is missing its `}` at the end of the scalar
+        let range = doc.diagnostics[0].range;
+        assert_eq!((range.start.line, range.start.character), (4, 27));
+    }
+
+    #[test]
+    fn reports_skill_lines_that_arent_strings() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Type: ZOMBIE\n  Skills:\n  - damage: 5\n"),
+        );
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_eq!(
+            doc.diagnostics[0].message,
+            "Invalid configuration file structure. Expected string, got a mapping"
+        );
+        assert_eq!(doc.diagnostics[0].range.start.line, 3);
+    }
+
+    #[test]
+    fn parses_merge_keys_without_errors() {
+        let server = TestServer::new();
+        let source = "base: &base\n  Type: ZOMBIE\n  Health: 20\nMob:\n  <<: *base\n  Health: 30\n";
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+        assert!(doc.diagnostics.is_empty());
+        let merge = doc
+            .semantic_tokens
+            .iter()
+            .find(|token| &source[token.start..token.start + token.length] == MERGE_KEY)
+            .expect("the merge key should be highlighted");
+        assert_eq!(
+            merge.token_type,
+            get_index_for_type(SemanticTokenType::OPERATOR)
+        );
+        // the alias is read as the mapping it refers to
+        let mob = doc
+            .yaml
+            .as_ref()
+            .unwrap()
+            .as_mapping()
+            .unwrap()
+            .get_mapping("Mob")
+            .unwrap();
+        let base = mob.get_mapping(MERGE_KEY).unwrap();
+        assert_eq!(base.get_scalar("Type").unwrap().as_str(), "ZOMBIE");
+    }
+
+    #[test]
+    fn resolves_aliased_skill_lists() {
+        let server = TestServer::new();
+        let source = "common: &common\n- damage{amount=1} @target\nMySkill:\n  Skills: *common\n";
+        let doc = server
+            .backend()
+            .update_document(&TestServer::uri("skills.yml"), source.to_string());
+        assert!(doc.diagnostics.is_empty());
+        let skill = doc
+            .yaml
+            .as_ref()
+            .unwrap()
+            .as_mapping()
+            .unwrap()
+            .get_mapping("MySkill");
+        let skills = skill
+            .unwrap()
+            .get_sequence("Skills")
+            .expect("the alias should be resolved");
+        assert_eq!(
+            skills.get_scalar(0).unwrap().as_str(),
+            "damage{amount=1} @target"
+        );
+        assert_eq!(doc.skill_definitions.len(), 1);
+    }
+
+    #[test]
+    fn keeps_anchor_characters_in_block_scalars() {
+        let source = "Item:\n  Lore: |\n    &7Sword of *fire\n  Name: &name Sword\n";
+        assert_eq!(
+            blank_anchors(source),
+            "Item:\n  Lore: |\n    &7Sword of *fire\n  Name:       Sword\n"
+        );
+    }
+
+    #[test]
+    fn parses_empty_documents_without_diagnostics() {
+        let server = TestServer::new();
+        for source in ["", "  \n\t\n"] {
+            let doc = server
+                .backend()
+                .update_document(&TestServer::uri("mobs.yml"), source.to_string());
+            assert!(doc.diagnostics.is_empty(), "{:?}", source);
+            assert!(doc.semantic_tokens.is_empty(), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn warns_about_duplicate_keys() {
+        let server = TestServer::new();
+        let doc = server.backend().update_document(
+            &TestServer::uri("mobs.yml"),
+            String::from("Mob:\n  Health: 20\n  Type: ZOMBIE\n  Health: 30\n"),
+        );
+        assert_eq!(doc.diagnostics.len(), 1);
+        let diagnostic = &doc.diagnostics[0];
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostic.range.start.line, 3);
+        // the related information points at the first `Health`
+        let related = diagnostic.related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.range.start.line, 1);
+    }
+}
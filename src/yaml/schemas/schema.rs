@@ -1,10 +1,13 @@
 use marked_yaml::Node::{self, Scalar};
 
-use crate::documents::DocumentInfo;
+use crate::{
+    documents::DocumentInfo, errors::error_registry::InvalidConfigurationFileStructureError,
+    utilities::positions_and_ranges::CustomRange,
+};
 
 pub trait YamlSchema {
     fn get_description(&self) -> String;
-    fn validate(&self, doc: &mut DocumentInfo, node: &Node) -> bool {
+    fn validate(&self, _doc: &mut DocumentInfo, _node: &Node) -> bool {
         true
     }
 }
@@ -27,16 +30,26 @@ impl YamlSchema for YamlSchemaString {
         }
     }
     fn validate(&self, doc: &mut DocumentInfo, node: &Node) -> bool {
-        match node {
-            Scalar(scalar) => {
-                if let Some(literal) = &self.literal {
-                    if scalar.to_string() != *literal {
-                        return false;
-                    }
-                }
-            }
-            _ => return false,
+        let valid = match node {
+            Scalar(scalar) => self
+                .literal
+                .as_ref()
+                .is_none_or(|literal| scalar.as_str() == literal),
+            _ => false,
+        };
+        if !valid {
+            let got = match node {
+                Scalar(scalar) => format!("\"{}\"", scalar.as_str()),
+                Node::Mapping(_) => String::from("a mapping"),
+                Node::Sequence(_) => String::from("a list"),
+            };
+            let error = InvalidConfigurationFileStructureError::new(
+                CustomRange::from_span(*node.span()),
+                got,
+                self.get_description(),
+            );
+            doc.push_diagnostic_dedup(error.to_error().to_diagnostic(&doc.uri));
         }
-        true
+        valid
     }
 }